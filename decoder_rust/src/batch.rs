@@ -0,0 +1,174 @@
+use std::{
+    fmt::{self, Display},
+    fs, io,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+use crate::{decode_with_offsets, instruction::Instruction, nasm};
+
+pub struct Summary {
+    pub instructions: usize,
+    pub unknown: usize,
+    pub bytes: usize,
+    /// `None` when round-tripping was skipped, else the first mismatching offset if any.
+    pub round_trip: Option<Result<Option<usize>, String>>,
+}
+
+pub struct FileReport {
+    pub path: PathBuf,
+    pub summary: Result<Summary, String>,
+}
+
+impl FileReport {
+    pub fn is_ok(&self) -> bool {
+        match &self.summary {
+            Ok(summary) => !matches!(summary.round_trip, Some(Err(_)) | Some(Ok(Some(_)))),
+            Err(_) => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BatchOptions {
+    /// Inputs are assembled binaries rather than `.asm` files.
+    pub raw: bool,
+    pub round_trip: bool,
+    pub jobs: usize,
+}
+
+/// Expands directories in `inputs` into the files beneath them, keeping only `.asm`
+/// files unless `raw` is set, sorted so reports are stable between runs.
+pub fn collect_inputs(inputs: &[PathBuf], raw: bool) -> Result<Vec<PathBuf>, io::Error> {
+    let mut files = vec![];
+
+    for input in inputs {
+        if input.is_dir() {
+            collect_dir(input, raw, &mut files)?;
+        } else {
+            files.push(input.clone());
+        }
+    }
+
+    Ok(files)
+}
+
+fn collect_dir(dir: &Path, raw: bool, files: &mut Vec<PathBuf>) -> Result<(), io::Error> {
+    let mut entries = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort();
+
+    for entry in entries {
+        if entry.is_dir() {
+            collect_dir(&entry, raw, files)?;
+        } else if raw
+            || entry
+                .extension()
+                .is_some_and(|extension| extension == "asm")
+        {
+            files.push(entry);
+        }
+    }
+
+    Ok(())
+}
+
+/// Summarizes every file on `options.jobs` threads. A file that fails to read or
+/// assemble, or that panics the decoder, is reported rather than ending the batch.
+pub fn run(files: &[PathBuf], options: BatchOptions) -> Vec<FileReport> {
+    let next = AtomicUsize::new(0);
+    let reports = Mutex::new(Vec::with_capacity(files.len()));
+
+    thread::scope(|scope| {
+        for _ in 0..options.jobs.max(1) {
+            scope.spawn(|| {
+                while let Some(path) = files.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    let summary = panic::catch_unwind(AssertUnwindSafe(|| {
+                        summarize(path, &options).map_err(|err| err.to_string())
+                    }))
+                    .unwrap_or_else(|_| Err("decoder panicked".to_string()));
+
+                    reports.lock().unwrap().push(FileReport {
+                        path: path.clone(),
+                        summary,
+                    });
+                }
+            });
+        }
+    });
+
+    let mut reports = reports.into_inner().unwrap();
+    reports.sort_by(|a, b| a.path.cmp(&b.path));
+    reports
+}
+
+fn summarize(path: &Path, options: &BatchOptions) -> Result<Summary, io::Error> {
+    let bytes = if options.raw {
+        fs::read(path)?
+    } else {
+        nasm::assemble(path)?
+    };
+
+    let lines = decode_with_offsets(&bytes);
+
+    Ok(Summary {
+        instructions: lines.len(),
+        unknown: lines
+            .iter()
+            .filter(|decoded| matches!(decoded.instruction, Instruction::Noop))
+            .count(),
+        bytes: bytes.len(),
+        round_trip: options
+            .round_trip
+            .then(|| nasm::round_trip(&bytes).map_err(|err| err.to_string())),
+    })
+}
+
+pub struct Report<'a>(pub &'a [FileReport]);
+
+impl Display for Report<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self
+            .0
+            .iter()
+            .map(|report| report.path.display().to_string().len())
+            .chain(Some(4))
+            .max()
+            .unwrap_or_default();
+
+        writeln!(
+            f,
+            "{:<width$}  {:>12}  {:>7}  {:>6}  round-trip",
+            "file", "instructions", "unknown", "bytes"
+        )?;
+
+        for report in self.0 {
+            let path = report.path.display().to_string();
+            match &report.summary {
+                Ok(summary) => {
+                    let round_trip = match &summary.round_trip {
+                        None => "skipped".to_string(),
+                        Some(Ok(None)) => "ok".to_string(),
+                        Some(Ok(Some(offset))) => format!("mismatch at {offset:#06x}"),
+                        Some(Err(err)) => format!("error: {}", err.replace('\n', "; ")),
+                    };
+                    writeln!(
+                        f,
+                        "{path:<width$}  {:>12}  {:>7}  {:>6}  {round_trip}",
+                        summary.instructions, summary.unknown, summary.bytes
+                    )?;
+                }
+                Err(err) => writeln!(f, "{path:<width$}  error: {}", err.replace('\n', "; "))?,
+            }
+        }
+
+        let failed = self.0.iter().filter(|report| !report.is_ok()).count();
+        write!(f, "\n{} files, {failed} failed", self.0.len())
+    }
+}
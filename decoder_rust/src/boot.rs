@@ -0,0 +1,119 @@
+use std::io;
+
+use crate::image::Image;
+
+/// Where the BIOS copies the boot sector before jumping to it.
+pub const BOOT_ADDRESS: usize = 0x7C00;
+
+pub const SECTOR_SIZE: usize = 512;
+
+const SIGNATURE: [u8; 2] = [0x55, 0xAA];
+
+/// Lays out a boot sector the way the BIOS leaves it, at 0000:7C00 with CS:IP
+/// pointing at its first byte. Code runs up to the last non-zero byte before the
+/// signature; the zero padding after that and the signature itself are data.
+pub fn load_boot_sector(sector: &[u8]) -> Result<Image, io::Error> {
+    if sector.len() != SECTOR_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "boot sector is {} bytes rather than {SECTOR_SIZE}",
+                sector.len()
+            ),
+        ));
+    }
+
+    let signature = &sector[SECTOR_SIZE - 2..];
+    if signature != SIGNATURE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "boot sector ends in {:02X} {:02X} rather than the 55 AA signature",
+                signature[0], signature[1]
+            ),
+        ));
+    }
+
+    let code_size = sector[..SECTOR_SIZE - 2]
+        .iter()
+        .rposition(|byte| *byte != 0)
+        .map_or(0, |last| last + 1);
+
+    let mut bytes = vec![0; BOOT_ADDRESS];
+    bytes.extend_from_slice(sector);
+
+    let code = BOOT_ADDRESS..BOOT_ADDRESS + code_size;
+    let padding = BOOT_ADDRESS + code_size..BOOT_ADDRESS + SECTOR_SIZE - 2;
+    let signature = BOOT_ADDRESS + SECTOR_SIZE - 2..BOOT_ADDRESS + SECTOR_SIZE;
+
+    Ok(Image {
+        bytes,
+        segment: 0,
+        code: vec![code],
+        data: [padding, signature]
+            .into_iter()
+            .filter(|range| !range.is_empty())
+            .collect(),
+        cs: 0,
+        ip: BOOT_ADDRESS as u16,
+        ss: 0,
+        sp: 0,
+        ds: 0,
+        es: 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{instruction::Width, sim::Segment};
+
+    /// mov ax, 1; ret, followed by padding and the signature.
+    fn sector() -> Vec<u8> {
+        let mut sector = vec![0; SECTOR_SIZE];
+        sector[..4].copy_from_slice(&[0xB8, 0x01, 0x00, 0xC3]);
+        sector[SECTOR_SIZE - 2..].copy_from_slice(&SIGNATURE);
+        sector
+    }
+
+    fn error(sector: &[u8]) -> String {
+        match load_boot_sector(sector) {
+            Ok(_) => panic!("loaded a bad boot sector"),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    #[test]
+    fn rejects_a_missing_signature_or_the_wrong_size() {
+        let mut unsigned = sector();
+        unsigned[SECTOR_SIZE - 1] = 0x55;
+
+        assert_eq!(
+            error(&unsigned),
+            "boot sector ends in 55 55 rather than the 55 AA signature"
+        );
+        assert_eq!(
+            error(&sector()[..SECTOR_SIZE - 1]),
+            "boot sector is 511 bytes rather than 512"
+        );
+    }
+
+    #[test]
+    fn code_stops_at_the_padding_before_the_signature() {
+        let image = load_boot_sector(&sector()).unwrap();
+        let code = 0x7C00..0x7C04;
+
+        assert_eq!(image.code, [code]);
+        assert_eq!(image.data, [0x7C04..0x7DFE, 0x7DFE..0x7E00]);
+    }
+
+    #[test]
+    fn runs_from_0000_7c00() {
+        let image = load_boot_sector(&sector()).unwrap();
+        let cpu = image.load();
+
+        assert_eq!((cpu.segment(Segment::CS), cpu.ip), (0, 0x7C00));
+        assert_eq!(cpu.read_memory(BOOT_ADDRESS, Width::Word), 0x01B8);
+        assert_eq!(cpu.read_memory(BOOT_ADDRESS - 1, Width::Byte), 0);
+    }
+}
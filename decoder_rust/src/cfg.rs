@@ -0,0 +1,265 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    ops::Range,
+};
+
+use crate::{
+    instruction::{Instruction, Operands},
+    Decoded,
+};
+
+/// A run of instructions that only control flow can enter at the top and leave
+/// at the bottom.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    /// Offset of the first instruction.
+    pub start: usize,
+    /// Offset just past the last instruction.
+    pub end: usize,
+    /// Indices of the block's instructions in [`Cfg::lines`].
+    pub lines: Range<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EdgeKind {
+    /// A jump or loop transferring control to its target.
+    Taken,
+    /// Execution carrying on to the next instruction, including a branch not taken.
+    Fallthrough,
+}
+
+/// Control passing from one block to another, both given as indices into [`Cfg::blocks`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub kind: EdgeKind,
+}
+
+/// Control-flow graph over decoded instructions. Calls are assumed to return, so
+/// they neither end a block nor get an edge, but their targets still start one.
+pub struct Cfg {
+    pub lines: Vec<Decoded>,
+    pub blocks: Vec<Block>,
+    pub edges: Vec<Edge>,
+}
+
+impl Cfg {
+    /// Builds the graph from `lines` in address order, as [`crate::image::Image::decode`]
+    /// and [`crate::traverse::traverse`] return them. Targets outside `lines` get no edge.
+    pub fn new(lines: Vec<Decoded>) -> Self {
+        let index_of: HashMap<usize, usize> = lines
+            .iter()
+            .enumerate()
+            .map(|(index, decoded)| (decoded.offset, index))
+            .collect();
+
+        let mut leaders = BTreeSet::from([0]);
+        for (index, decoded) in lines.iter().enumerate() {
+            if let Some(target) = decoded
+                .jump_target()
+                .and_then(|target| index_of.get(&target))
+            {
+                leaders.insert(*target);
+            }
+            if ends_block(&decoded.instruction) || !falls_into_next(&lines, index) {
+                leaders.insert(index + 1);
+            }
+        }
+        leaders.retain(|index| *index < lines.len());
+
+        let bounds: Vec<usize> = leaders.into_iter().chain(Some(lines.len())).collect();
+        let blocks: Vec<Block> = bounds
+            .windows(2)
+            .map(|pair| Block {
+                start: lines[pair[0]].offset,
+                end: lines[pair[1] - 1].offset + lines[pair[1] - 1].size,
+                lines: pair[0]..pair[1],
+            })
+            .collect();
+
+        let block_at: HashMap<usize, usize> = blocks
+            .iter()
+            .enumerate()
+            .map(|(index, block)| (block.start, index))
+            .collect();
+
+        let mut edges = vec![];
+        for (from, block) in blocks.iter().enumerate() {
+            let last = &lines[block.lines.end - 1];
+
+            if !matches!(last.instruction, Instruction::Call { .. }) {
+                if let Some(to) = last.jump_target().and_then(|target| block_at.get(&target)) {
+                    edges.push(Edge {
+                        from,
+                        to: *to,
+                        kind: EdgeKind::Taken,
+                    });
+                }
+            }
+
+            if let Some(to) = last.fallthrough().and_then(|next| block_at.get(&next)) {
+                edges.push(Edge {
+                    from,
+                    to: *to,
+                    kind: EdgeKind::Fallthrough,
+                });
+            }
+        }
+
+        Self {
+            lines,
+            blocks,
+            edges,
+        }
+    }
+
+    /// Index of the block starting at `offset`.
+    pub fn block_at(&self, offset: usize) -> Option<usize> {
+        self.block_containing(offset)
+            .filter(|block| self.blocks[*block].start == offset)
+    }
+
+    /// Index of the block whose instructions cover `offset`.
+    pub fn block_containing(&self, offset: usize) -> Option<usize> {
+        let after = self.blocks.partition_point(|block| block.start <= offset);
+        let block = after.checked_sub(1)?;
+        (offset < self.blocks[block].end).then_some(block)
+    }
+
+    /// Index into [`Cfg::lines`] of the instruction starting at `offset`.
+    pub fn line_at(&self, offset: usize) -> Option<usize> {
+        let index = self
+            .lines
+            .partition_point(|decoded| decoded.offset < offset);
+        self.lines
+            .get(index)
+            .filter(|decoded| decoded.offset == offset)
+            .map(|_| index)
+    }
+
+    /// Blocks control can arrive at without an edge: the first block, call
+    /// targets, and any block nothing jumps or falls into.
+    pub fn entries(&self) -> BTreeSet<usize> {
+        let calls = self
+            .lines
+            .iter()
+            .filter(|decoded| matches!(decoded.instruction, Instruction::Call { .. }))
+            .filter_map(|decoded| decoded.jump_target())
+            .filter_map(|target| self.block_at(target));
+        let unreached =
+            (0..self.blocks.len()).filter(|block| self.predecessors(*block).next().is_none());
+
+        (0..self.blocks.len().min(1))
+            .chain(calls)
+            .chain(unreached)
+            .collect()
+    }
+
+    pub fn instructions(&self, block: usize) -> &[Decoded] {
+        &self.lines[self.blocks[block].lines.clone()]
+    }
+
+    /// Edges leaving `block`, the taken edge before the fallthrough.
+    pub fn successors(&self, block: usize) -> impl Iterator<Item = &Edge> {
+        self.edges.iter().filter(move |edge| edge.from == block)
+    }
+
+    pub fn predecessors(&self, block: usize) -> impl Iterator<Item = &Edge> {
+        self.edges.iter().filter(move |edge| edge.to == block)
+    }
+}
+
+/// Whether control might not reach the next instruction: any jump, loop or return.
+fn ends_block(instruction: &Instruction) -> bool {
+    match instruction {
+        Instruction::Call { .. } => false,
+        _ => {
+            !instruction.falls_through() || matches!(instruction.operands(), Operands::Jump { .. })
+        }
+    }
+}
+
+/// Whether the instruction after `lines[index]` starts right where it ends.
+fn falls_into_next(lines: &[Decoded], index: usize) -> bool {
+    lines
+        .get(index + 1)
+        .is_some_and(|next| next.offset == lines[index].offset + lines[index].size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode_with_offsets;
+
+    /// mov cx, 3; add ax, cx; sub cx, 1; jne 3; ret
+    const COUNTDOWN: [u8; 11] = [
+        0xB9, 0x03, 0x00, 0x01, 0xC8, 0x83, 0xE9, 0x01, 0x75, 0xF9, 0xC3,
+    ];
+
+    fn edges(cfg: &Cfg) -> Vec<(usize, usize, EdgeKind)> {
+        cfg.edges
+            .iter()
+            .map(|edge| (edge.from, edge.to, edge.kind))
+            .collect()
+    }
+
+    #[test]
+    fn branch_targets_and_fallthroughs_split_blocks() {
+        let cfg = Cfg::new(decode_with_offsets(&COUNTDOWN));
+
+        let bounds: Vec<(usize, usize)> = cfg
+            .blocks
+            .iter()
+            .map(|block| (block.start, block.end))
+            .collect();
+        assert_eq!(bounds, vec![(0, 3), (3, 10), (10, 11)]);
+        assert_eq!(
+            edges(&cfg),
+            vec![
+                (0, 1, EdgeKind::Fallthrough),
+                (1, 1, EdgeKind::Taken),
+                (1, 2, EdgeKind::Fallthrough),
+            ]
+        );
+        assert_eq!(cfg.block_containing(7), Some(1));
+        assert_eq!(cfg.block_at(7), None);
+    }
+
+    #[test]
+    fn calls_start_a_block_at_their_target_without_an_edge() {
+        // call 4; ret; mov ax, 5; ret
+        let cfg = Cfg::new(decode_with_offsets(&[
+            0xE8, 0x01, 0x00, 0xC3, 0xB8, 0x05, 0x00, 0xC3,
+        ]));
+
+        assert_eq!(cfg.blocks.len(), 2);
+        assert_eq!(cfg.blocks[0].lines, 0..2);
+        assert!(edges(&cfg).is_empty());
+        assert_eq!(cfg.entries(), BTreeSet::from([0, 1]));
+    }
+
+    #[test]
+    fn lookups_miss_gaps_and_the_middle_of_instructions() {
+        // ret; then past a gap, mov ax, 5; ret
+        let mut lines = decode_with_offsets(&[0xC3]);
+        lines.extend(
+            decode_with_offsets(&[0xB8, 0x05, 0x00, 0xC3])
+                .into_iter()
+                .map(|decoded| Decoded {
+                    offset: decoded.offset + 8,
+                    ..decoded
+                }),
+        );
+        let cfg = Cfg::new(lines);
+
+        assert_eq!(cfg.block_containing(0), Some(0));
+        assert_eq!(cfg.block_containing(4), None);
+        assert_eq!(cfg.block_containing(11), Some(1));
+        assert_eq!(cfg.block_containing(12), None);
+        assert_eq!(cfg.block_at(8), Some(1));
+        assert_eq!(cfg.line_at(11), Some(2));
+        assert_eq!(cfg.line_at(9), None);
+        assert_eq!(cfg.line_at(20), None);
+    }
+}
@@ -0,0 +1,384 @@
+use std::fmt::{self, Display};
+
+use crate::{
+    cfg::Cfg,
+    effects,
+    instruction::{Instruction, Location, Operands, Register, Width},
+    sim::{immediate_value, mask},
+};
+
+const WORD_REGISTERS: [Register; 8] = [
+    Register::AX,
+    Register::CX,
+    Register::DX,
+    Register::BX,
+    Register::SP,
+    Register::BP,
+    Register::SI,
+    Register::DI,
+];
+
+/// Register values known at one point in the program, tracked a byte at a time
+/// so that `mov al, 1` leaves whatever was known about `ah` alone.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Constants {
+    bytes: [Option<u8>; 16],
+}
+
+impl Constants {
+    /// Nothing known, as at an entry point with no assumptions about the caller.
+    pub fn unknown() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, register: Register) -> Option<u16> {
+        let (index, width) = slot(register);
+        match width {
+            Width::Byte => self.bytes[index].map(u16::from),
+            Width::Word => {
+                let lo = self.bytes[index]?;
+                let hi = self.bytes[index + 1]?;
+                Some(u16::from_le_bytes([lo, hi]))
+            }
+        }
+    }
+
+    pub fn set(&mut self, register: Register, value: Option<u16>) {
+        let (index, width) = slot(register);
+        let [lo, hi] = value.map_or([None, None], |value| value.to_le_bytes().map(Some));
+
+        self.bytes[index] = lo;
+        if width == Width::Word {
+            self.bytes[index + 1] = hi;
+        }
+    }
+
+    /// The offset a memory operand refers to, if its base and index are known.
+    pub fn effective_address(&self, location: &Location) -> Option<u16> {
+        if !location.is_mem_addr {
+            return None;
+        }
+
+        let base = match location.register {
+            Some(register) => self.get(register)?,
+            None => 0,
+        };
+        let index = match location.addr_calc {
+            Some(register) => self.get(register)?,
+            None => 0,
+        };
+        let displacement = location.displacement.unwrap_or(0) as u16;
+
+        Some(base.wrapping_add(index).wrapping_add(displacement))
+    }
+
+    /// Keeps only what both `self` and `other` agree on, for where paths join.
+    fn meet(&mut self, other: &Constants) {
+        for (mine, theirs) in self.bytes.iter_mut().zip(other.bytes) {
+            if *mine != theirs {
+                *mine = None;
+            }
+        }
+    }
+
+    fn value(&self, location: &Location) -> Option<u16> {
+        match location.register {
+            Some(register) if !location.is_mem_addr => self.get(register),
+            _ => None,
+        }
+    }
+
+    /// Moves past `instruction`, forgetting anything it might change in ways the
+    /// pass doesn't follow.
+    pub fn step(&mut self, instruction: &Instruction) {
+        let (dest, value) = match instruction.operands() {
+            Operands::Locations { src, dest } => {
+                let width = dest.width().or(src.width()).unwrap_or(Width::Word);
+                let value = match instruction {
+                    Instruction::Mov { .. } => self.value(src),
+                    Instruction::Add { .. } => self.combine(dest, src, width, u16::wrapping_add),
+                    Instruction::Sub { .. } => self.combine(dest, src, width, u16::wrapping_sub),
+                    // Zero whatever the register held.
+                    Instruction::Xor { .. }
+                        if !src.is_mem_addr && src.register == dest.register =>
+                    {
+                        Some(0)
+                    }
+                    Instruction::Xor { .. } => self.combine(dest, src, width, |a, b| a ^ b),
+                    _ => return,
+                };
+                (dest, value)
+            }
+            Operands::Immediate { data, dest } => {
+                let width = dest.width().unwrap_or(data.width());
+                let data = immediate_value(data) & mask(width);
+                let value = match instruction {
+                    Instruction::MovImmediate { .. } => Some(data),
+                    Instruction::AddImmediate { .. } => self
+                        .value(dest)
+                        .map(|value| value.wrapping_add(data) & mask(width)),
+                    Instruction::SubImmediate { .. } => self
+                        .value(dest)
+                        .map(|value| value.wrapping_sub(data) & mask(width)),
+                    Instruction::XorImmediate { .. } => self.value(dest).map(|value| value ^ data),
+                    _ => return,
+                };
+                (dest, value)
+            }
+            Operands::Unary { width, dest } => {
+                let value = self.value(dest).map(|value| match instruction {
+                    Instruction::Inc { .. } => value.wrapping_add(1) & mask(width),
+                    _ => value.wrapping_sub(1) & mask(width),
+                });
+                (dest, value)
+            }
+            Operands::Stack { location } => {
+                let sp = self.get(Register::SP).map(|sp| match instruction {
+                    Instruction::Push { .. } => sp.wrapping_sub(2),
+                    _ => sp.wrapping_add(2),
+                });
+                self.set(Register::SP, sp);
+
+                // Whatever was on the stack isn't tracked.
+                if let (Instruction::Pop { .. }, Some(register)) = (instruction, location.register)
+                {
+                    self.set(register, None);
+                }
+                return;
+            }
+            Operands::Jump { .. } => {
+                match instruction {
+                    Instruction::Loop { .. }
+                    | Instruction::Loopz { .. }
+                    | Instruction::Loopnz { .. } => {
+                        let cx = self.get(Register::CX).map(|cx| cx.wrapping_sub(1));
+                        self.set(Register::CX, cx);
+                    }
+                    // The callee could leave anything anywhere.
+                    Instruction::Call { .. } => *self = Self::unknown(),
+                    _ => {}
+                }
+                return;
+            }
+            Operands::None => {
+                if let Instruction::Noop = instruction {
+                    *self = Self::unknown();
+                }
+                return;
+            }
+        };
+
+        if let Some(register) = dest.register.filter(|_| !dest.is_mem_addr) {
+            self.set(register, value);
+        }
+    }
+
+    fn combine(
+        &self,
+        dest: &Location,
+        src: &Location,
+        width: Width,
+        op: fn(u16, u16) -> u16,
+    ) -> Option<u16> {
+        Some(op(self.value(dest)?, self.value(src)?) & mask(width))
+    }
+
+    /// The known registers, word registers whole when both halves are known.
+    pub fn known(&self) -> Vec<(Register, u16)> {
+        let mut known = vec![];
+        for register in WORD_REGISTERS {
+            if let Some(value) = self.get(register) {
+                known.push((register, value));
+                continue;
+            }
+            for half in halves(register) {
+                if let Some(value) = self.get(*half) {
+                    known.push((*half, value));
+                }
+            }
+        }
+        known
+    }
+}
+
+impl Display for Constants {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let known: Vec<String> = self
+            .known()
+            .into_iter()
+            .map(|(register, value)| format!("{register} = {}", describe(register, value)))
+            .collect();
+        write!(f, "{}", known.join(", "))
+    }
+}
+
+/// What is known on either side of each instruction, indexed like [`Cfg::lines`].
+pub struct Propagation {
+    pub before: Vec<Constants>,
+    pub after: Vec<Constants>,
+}
+
+/// Forward dataflow from `entry` at each of [`Cfg::entries`], keeping a value
+/// only where every path into an instruction agrees on it.
+pub fn propagate(cfg: &Cfg, entry: &Constants) -> Propagation {
+    let entries = cfg.entries();
+    let mut block_in: Vec<Option<Constants>> = vec![None; cfg.blocks.len()];
+    for block in &entries {
+        block_in[*block] = Some(entry.clone());
+    }
+
+    let mut before = vec![Constants::unknown(); cfg.lines.len()];
+    let mut after = vec![Constants::unknown(); cfg.lines.len()];
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+
+        for (index, block) in cfg.blocks.iter().enumerate() {
+            let Some(mut state) = block_in[index].clone() else {
+                continue;
+            };
+
+            for line in block.lines.clone() {
+                before[line] = state.clone();
+                state.step(&cfg.lines[line].instruction);
+                after[line] = state.clone();
+            }
+
+            for edge in cfg.successors(index) {
+                let next = match &block_in[edge.to] {
+                    None => state.clone(),
+                    Some(current) => {
+                        let mut next = current.clone();
+                        next.meet(&state);
+                        next
+                    }
+                };
+
+                if block_in[edge.to].as_ref() != Some(&next) {
+                    block_in[edge.to] = Some(next);
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    Propagation { before, after }
+}
+
+/// Listing notes: the known value of every register an instruction writes, and
+/// the address behind any memory operand whose registers are known.
+pub fn annotations(cfg: &Cfg, propagation: &Propagation) -> Vec<(usize, String)> {
+    let mut notes = vec![];
+
+    for (index, decoded) in cfg.lines.iter().enumerate() {
+        let mut parts = vec![];
+
+        let before = &propagation.before[index];
+        if let Some(location) = decoded.instruction.memory_operand() {
+            let has_registers = location.register.is_some();
+            if let Some(address) = before.effective_address(location).filter(|_| has_registers) {
+                parts.push(format!("{location} at {address:#06x}"));
+            }
+        }
+
+        let after = &propagation.after[index];
+        for register in effects::writes(&decoded.instruction).registers {
+            if let Some(value) = after.get(register) {
+                parts.push(format!("{register} = {}", describe(register, value)));
+            }
+        }
+
+        if !parts.is_empty() {
+            notes.push((decoded.offset, parts.join(", ")));
+        }
+    }
+
+    notes
+}
+
+fn describe(register: Register, value: u16) -> String {
+    match register.width() {
+        Width::Byte => format!("{value:#04x} ({value})"),
+        Width::Word => format!("{value:#06x} ({value})"),
+    }
+}
+
+/// Index of the register's low byte, and how many bytes it spans.
+fn slot(register: Register) -> (usize, Width) {
+    let word = WORD_REGISTERS
+        .iter()
+        .position(|word| *word == register.full())
+        .expect("every register is part of a word register");
+
+    match register {
+        Register::AH | Register::CH | Register::DH | Register::BH => (word * 2 + 1, Width::Byte),
+        _ => (word * 2, register.width()),
+    }
+}
+
+fn halves(register: Register) -> &'static [Register] {
+    match register {
+        Register::AX => &[Register::AL, Register::AH],
+        Register::CX => &[Register::CL, Register::CH],
+        Register::DX => &[Register::DL, Register::DH],
+        Register::BX => &[Register::BL, Register::BH],
+        _ => &[],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode_with_offsets;
+
+    fn propagation(bytes: &[u8]) -> (Cfg, Propagation) {
+        let cfg = Cfg::new(decode_with_offsets(bytes));
+        let propagation = propagate(&cfg, &Constants::unknown());
+        (cfg, propagation)
+    }
+
+    #[test]
+    fn known_registers_resolve_memory_operands() {
+        // mov bx, 0x10; mov si, 2; mov ax, [bx + si + 4]
+        let (cfg, propagation) =
+            propagation(&[0xBB, 0x10, 0x00, 0xBE, 0x02, 0x00, 0x8B, 0x40, 0x04]);
+        let location = cfg.lines[2].instruction.memory_operand().unwrap();
+
+        assert_eq!(
+            propagation.before[2].effective_address(location),
+            Some(0x16)
+        );
+        assert_eq!(propagation.after[2].get(Register::AX), None);
+    }
+
+    #[test]
+    fn bytes_are_tracked_separately() {
+        // mov ax, 0x1234; mov al, 0x56; add ah, 1
+        let (_, propagation) = propagation(&[0xB8, 0x34, 0x12, 0xB0, 0x56, 0x80, 0xC4, 0x01]);
+
+        assert_eq!(propagation.after[1].get(Register::AX), Some(0x1256));
+        assert_eq!(propagation.after[2].get(Register::AX), Some(0x1356));
+    }
+
+    #[test]
+    fn paths_keep_only_what_they_agree_on() {
+        // mov bx, 2; mov cx, 7; cmp ax, 0; je 14; mov bx, 1; ret
+        let (_, propagation) = propagation(&[
+            0xBB, 0x02, 0x00, 0xB9, 0x07, 0x00, 0x83, 0xF8, 0x00, 0x74, 0x03, 0xBB, 0x01, 0x00,
+            0xC3,
+        ]);
+
+        assert_eq!(propagation.before[5].get(Register::BX), None);
+        assert_eq!(propagation.before[5].get(Register::CX), Some(7));
+    }
+
+    #[test]
+    fn calls_forget_everything() {
+        // mov ax, 1; call 6; ret
+        let (_, propagation) = propagation(&[0xB8, 0x01, 0x00, 0xE8, 0x00, 0x00, 0xC3]);
+
+        assert_eq!(propagation.before[1].get(Register::AX), Some(1));
+        assert_eq!(propagation.after[1], Constants::unknown());
+    }
+}
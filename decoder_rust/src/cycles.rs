@@ -0,0 +1,311 @@
+use std::fmt::{self, Display};
+
+use crate::instruction::{Instruction, Location, Operands, Register, Width};
+
+/// 8086 clock estimate for one instruction, split the way the Intel manual tables are.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Cycles {
+    pub base: u32,
+    /// Effective address calculation for the memory operand, if any.
+    pub ea: u32,
+    /// The 4-clock penalty for each word transfer through an odd address.
+    pub penalty: u32,
+}
+
+impl Cycles {
+    pub fn total(&self) -> u32 {
+        self.base + self.ea + self.penalty
+    }
+}
+
+impl Display for Cycles {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.total())?;
+        if self.ea > 0 || self.penalty > 0 {
+            write!(f, " ({}", self.base)?;
+            if self.ea > 0 {
+                write!(f, " + {}ea", self.ea)?;
+            }
+            if self.penalty > 0 {
+                write!(f, " + {}p", self.penalty)?;
+            }
+            write!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
+/// Estimates `instruction`, with `taken` picking between the two timings of a
+/// jump or loop. Odd-address penalties depend on runtime addresses, so they are
+/// left for callers that know them; see [`word_transfers`].
+pub fn estimate(instruction: &Instruction, taken: bool) -> Cycles {
+    let (src, dest) = match instruction.operands() {
+        Operands::Locations { src, dest } => (Some(src), dest),
+        Operands::Immediate { dest, .. } => (None, dest),
+        Operands::Stack { location } => {
+            let base = match (instruction, location.is_mem_addr) {
+                (Instruction::Push { .. }, false) => 11,
+                (Instruction::Push { .. }, true) => 16,
+                (_, false) => 8,
+                (_, true) => 17,
+            };
+            return Cycles {
+                base,
+                ea: instruction
+                    .memory_operand()
+                    .map_or(0, effective_address_cycles),
+                penalty: 0,
+            };
+        }
+        Operands::Unary { width, dest } => {
+            let base = match (dest.is_mem_addr, width) {
+                (true, _) => 15,
+                (false, Width::Word) => 2,
+                (false, Width::Byte) => 3,
+            };
+            return Cycles {
+                base,
+                ea: instruction
+                    .memory_operand()
+                    .map_or(0, effective_address_cycles),
+                penalty: 0,
+            };
+        }
+        Operands::Jump { .. } => {
+            let base = match (instruction, taken) {
+                (Instruction::Loop { .. }, true) => 17,
+                (Instruction::Loop { .. }, false) => 5,
+                (Instruction::Loopz { .. }, true) => 18,
+                (Instruction::Loopz { .. }, false) => 6,
+                (Instruction::Loopnz { .. }, true) => 19,
+                (Instruction::Loopnz { .. }, false) => 5,
+                (Instruction::Jcxz { .. }, true) => 18,
+                (Instruction::Jcxz { .. }, false) => 6,
+                (Instruction::Jmp { .. }, _) => 15,
+                (Instruction::Call { .. }, _) => 19,
+                (_, true) => 16,
+                (_, false) => 4,
+            };
+            return Cycles {
+                base,
+                ..Default::default()
+            };
+        }
+        Operands::None => {
+            let base = match instruction {
+                Instruction::Ret => 8,
+                _ => 0,
+            };
+            return Cycles {
+                base,
+                ..Default::default()
+            };
+        }
+    };
+
+    let ea = instruction
+        .memory_operand()
+        .map_or(0, effective_address_cycles);
+
+    // The accumulator has dedicated direct-address encodings that skip the EA.
+    if matches!(instruction, Instruction::Mov { .. }) && is_accumulator_direct(src, dest) {
+        return Cycles {
+            base: 10,
+            ..Default::default()
+        };
+    }
+
+    let base = match (
+        instruction,
+        src.map(|src| src.is_mem_addr),
+        dest.is_mem_addr,
+    ) {
+        (Instruction::Mov { .. }, Some(false), false) => 2,
+        (Instruction::Mov { .. }, Some(true), false) => 8,
+        (Instruction::Mov { .. }, Some(false), true) => 9,
+        (Instruction::MovImmediate { .. }, None, false) => 4,
+        (Instruction::MovImmediate { .. }, None, true) => 10,
+
+        (Instruction::Add { .. } | Instruction::Sub { .. }, Some(false), false) => 3,
+        (Instruction::Add { .. } | Instruction::Sub { .. }, Some(true), false) => 9,
+        (Instruction::Add { .. } | Instruction::Sub { .. }, Some(false), true) => 16,
+        (Instruction::AddImmediate { .. } | Instruction::SubImmediate { .. }, None, false) => 4,
+        (Instruction::AddImmediate { .. } | Instruction::SubImmediate { .. }, None, true) => 17,
+
+        (Instruction::Xor { .. }, Some(false), false) => 3,
+        (Instruction::Xor { .. }, Some(true), false) => 9,
+        (Instruction::Xor { .. }, Some(false), true) => 16,
+        (Instruction::XorImmediate { .. }, None, false) => 4,
+        (Instruction::XorImmediate { .. }, None, true) => 17,
+
+        (Instruction::Cmp { .. }, Some(false), false) => 3,
+        (Instruction::Cmp { .. }, Some(true), false) => 9,
+        (Instruction::Cmp { .. }, Some(false), true) => 9,
+        (Instruction::CmpImmediate { .. }, None, false) => 4,
+        (Instruction::CmpImmediate { .. }, None, true) => 10,
+
+        (Instruction::Test { .. }, Some(false), false) => 3,
+        (Instruction::Test { .. }, Some(_), _) => 9,
+        // Only the accumulator's short encoding matches the other immediates.
+        (Instruction::TestImmediate { .. }, None, false) if is_accumulator(dest) => 4,
+        (Instruction::TestImmediate { .. }, None, false) => 5,
+        (Instruction::TestImmediate { .. }, None, true) => 11,
+
+        _ => 0,
+    };
+
+    Cycles {
+        base,
+        ea,
+        penalty: 0,
+    }
+}
+
+/// How many times `instruction` moves a word across the bus through its memory
+/// operand: once for loads, stores and compares, twice for read-modify-write.
+pub fn word_transfers(instruction: &Instruction) -> u32 {
+    let width = match instruction.operands() {
+        Operands::Locations { src, dest } => dest.width().or(src.width()),
+        Operands::Immediate { data, .. } => Some(data.width()),
+        Operands::Unary { width, .. } => Some(width),
+        _ => None,
+    };
+
+    if instruction.memory_operand().is_none() || width != Some(Width::Word) {
+        return 0;
+    }
+
+    match instruction {
+        Instruction::Add { src, .. }
+        | Instruction::Sub { src, .. }
+        | Instruction::Xor { src, .. }
+            if !src.is_mem_addr =>
+        {
+            2
+        }
+        Instruction::AddImmediate { .. }
+        | Instruction::SubImmediate { .. }
+        | Instruction::XorImmediate { .. } => 2,
+        Instruction::Inc { .. } | Instruction::Dec { .. } => 2,
+        _ => 1,
+    }
+}
+
+pub fn effective_address_cycles(location: &Location) -> u32 {
+    let displacement = location
+        .displacement
+        .is_some_and(|displacement| displacement != 0);
+
+    match (location.register, location.addr_calc, displacement) {
+        (None, _, _) => 6,
+        (Some(_), None, false) => 5,
+        (Some(_), None, true) => 9,
+        (Some(Register::BP), Some(Register::DI), false)
+        | (Some(Register::BX), Some(Register::SI), false) => 7,
+        (Some(_), Some(_), false) => 8,
+        (Some(Register::BP), Some(Register::DI), true)
+        | (Some(Register::BX), Some(Register::SI), true) => 11,
+        (Some(_), Some(_), true) => 12,
+    }
+}
+
+fn is_accumulator(location: &Location) -> bool {
+    !location.is_mem_addr && matches!(location.register, Some(Register::AX | Register::AL))
+}
+
+fn is_accumulator_direct(src: Option<&Location>, dest: &Location) -> bool {
+    let is_direct = |location: &Location| location.is_mem_addr && location.register.is_none();
+
+    match src {
+        Some(src) => {
+            (is_direct(src) && is_accumulator(dest)) || (is_accumulator(src) && is_direct(dest))
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode;
+
+    fn totals(bytes: &[u8], taken: bool) -> Vec<u32> {
+        decode(bytes.to_vec())
+            .iter()
+            .map(|instruction| estimate(instruction, taken).total())
+            .collect()
+    }
+
+    #[test]
+    fn memory_operands_add_their_effective_address_time() {
+        let bytes = [
+            0x8B, 0x1B, // mov bx, [bp + di]
+            0x89, 0x48, 0x04, // mov [bx + si + 4], cx
+            0x03, 0x41, 0x02, // add ax, [bx + di + 2]
+            0x8B, 0x0E, 0x10, 0x00, // mov cx, [16]
+            0x83, 0x07, 0x01, // add word [bx], 1
+            0x39, 0x76, 0x02, // cmp [bp + 2], si
+        ];
+
+        assert_eq!(totals(&bytes, false), [15, 20, 21, 14, 22, 18]);
+    }
+
+    #[test]
+    fn accumulator_direct_moves_skip_the_effective_address() {
+        // mov ax, [0x1000]; mov [0x1000], al
+        let instructions = decode(vec![0xA1, 0x00, 0x10, 0xA2, 0x00, 0x10]);
+
+        for instruction in &instructions {
+            assert_eq!(
+                estimate(instruction, false),
+                Cycles {
+                    base: 10,
+                    ..Default::default()
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn jumps_and_loops_depend_on_whether_they_are_taken() {
+        // jne 0; loop 0; loopz 0; loopnz 0; jcxz 0
+        let bytes = [0x75, 0xFE, 0xE2, 0xFC, 0xE1, 0xFA, 0xE0, 0xF8, 0xE3, 0xF6];
+
+        assert_eq!(totals(&bytes, true), [16, 17, 18, 19, 18]);
+        assert_eq!(totals(&bytes, false), [4, 5, 6, 5, 6]);
+    }
+
+    #[test]
+    fn read_modify_write_moves_words_twice() {
+        let instructions = decode(vec![
+            0x89, 0x05, // mov [di], ax
+            0x01, 0x05, // add [di], ax
+            0x83, 0x07, 0x01, // add word [bx], 1
+            0x80, 0x07, 0x01, // add byte [bx], 1
+            0x01, 0xC0, // add ax, ax
+        ]);
+
+        let transfers: Vec<u32> = instructions.iter().map(word_transfers).collect();
+
+        assert_eq!(transfers, [1, 2, 2, 0, 0]);
+    }
+
+    #[test]
+    fn display_breaks_out_the_parts() {
+        let cycles = Cycles {
+            base: 9,
+            ea: 12,
+            penalty: 4,
+        };
+
+        assert_eq!(cycles.to_string(), "25 (9 + 12ea + 4p)");
+        assert_eq!(
+            Cycles {
+                base: 4,
+                ..Default::default()
+            }
+            .to_string(),
+            "4"
+        );
+    }
+}
@@ -0,0 +1,302 @@
+use std::io;
+
+use crate::{
+    image::Image,
+    sim::{physical, MEMORY_SIZE},
+};
+
+/// The Program Segment Prefix DOS puts in front of every program.
+pub const PSP_SIZE: usize = 0x100;
+
+/// What DOS reports as the first segment past the program's memory.
+const TOP_OF_MEMORY: u16 = 0xA000;
+
+/// A .COM program shares one segment with its PSP and a stack at the very top.
+const MAX_COM_SIZE: usize = 0x10000 - PSP_SIZE - 2;
+
+/// Lays out a .COM program the way DOS loads it: PSP at `segment:0`, the image
+/// at `segment:0100`, every segment register at `segment` and SP at the top of
+/// the segment with a zero word pushed, so a final `ret` lands on the `int 20h`.
+pub fn load_com(code: &[u8], segment: u16, command_tail: &str) -> Result<Image, io::Error> {
+    if code.len() > MAX_COM_SIZE {
+        return Err(invalid(format!(
+            ".COM image is {} bytes, more than the {MAX_COM_SIZE} that fit in a segment",
+            code.len()
+        )));
+    }
+
+    let mut bytes = psp(command_tail)?;
+    bytes.extend_from_slice(code);
+    check_fits(&bytes, segment)?;
+
+    let code = PSP_SIZE..bytes.len();
+
+    Ok(Image {
+        bytes,
+        code: vec![code],
+        data: vec![],
+        segment,
+        cs: segment,
+        ip: PSP_SIZE as u16,
+        ss: segment,
+        sp: 0xFFFE,
+        ds: segment,
+        es: segment,
+    })
+}
+
+/// A minimal PSP: `int 20h` at offset 0, the top of memory at 2 and the command
+/// tail at 80h as a length byte followed by the text and a carriage return.
+pub fn psp(command_tail: &str) -> Result<Vec<u8>, io::Error> {
+    // The length byte, the terminating carriage return and the tail share 80h..100h.
+    if command_tail.len() > 126 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "command tail is longer than the 126 bytes the PSP holds",
+        ));
+    }
+
+    let mut psp = vec![0; PSP_SIZE];
+
+    psp[0x00..0x02].copy_from_slice(&[0xCD, 0x20]);
+    psp[0x02..0x04].copy_from_slice(&TOP_OF_MEMORY.to_le_bytes());
+
+    psp[0x80] = command_tail.len() as u8;
+    psp[0x81..0x81 + command_tail.len()].copy_from_slice(command_tail.as_bytes());
+    psp[0x81 + command_tail.len()] = 0x0D;
+
+    Ok(psp)
+}
+
+/// The fixed part of an MZ executable's header, with sizes already converted to bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MzHeader {
+    pub header_size: usize,
+    /// Length of the load module: everything in the file after the header.
+    pub image_size: usize,
+    pub min_alloc: usize,
+    pub ss: u16,
+    pub sp: u16,
+    pub ip: u16,
+    pub cs: u16,
+    /// Segment:offset pairs, relative to the load module, of words to relocate.
+    pub relocations: Vec<(u16, u16)>,
+}
+
+impl MzHeader {
+    pub fn parse(file: &[u8]) -> Result<Self, io::Error> {
+        let word = |offset: usize| -> Result<u16, io::Error> {
+            file.get(offset..offset + 2)
+                .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+                .ok_or_else(|| invalid(format!("MZ header truncated at {offset:#x}")))
+        };
+
+        if file.get(0..2) != Some(b"MZ") && file.get(0..2) != Some(b"ZM") {
+            return Err(invalid("missing MZ signature".to_string()));
+        }
+
+        let last_page = word(0x02)? as usize;
+        let pages = word(0x04)? as usize;
+        let relocation_count = word(0x06)? as usize;
+        let header_size = word(0x08)? as usize * 16;
+        let min_alloc = word(0x0A)? as usize * 16;
+        let relocation_table = word(0x18)? as usize;
+
+        let file_size = match last_page {
+            0 => pages * 512,
+            _ => pages.saturating_sub(1) * 512 + last_page,
+        };
+
+        if file_size > file.len() || header_size > file_size {
+            return Err(invalid(format!(
+                "MZ header describes {file_size} bytes with a {header_size} byte header, \
+                 but the file is {} bytes",
+                file.len()
+            )));
+        }
+
+        let relocations = (0..relocation_count)
+            .map(|index| {
+                let entry = relocation_table + index * 4;
+                Ok((word(entry + 2)?, word(entry)?))
+            })
+            .collect::<Result<_, io::Error>>()?;
+
+        Ok(Self {
+            header_size,
+            image_size: file_size - header_size,
+            min_alloc,
+            ss: word(0x0E)?,
+            sp: word(0x10)?,
+            ip: word(0x14)?,
+            cs: word(0x16)?,
+            relocations,
+        })
+    }
+}
+
+/// Lays out an MZ executable the way DOS loads it: PSP at `segment:0`, the load
+/// module right after at `segment + 10h` with every relocation adjusted by that
+/// start segment, and CS:IP and SS:SP taken from the header relative to it.
+/// Code is decoded from the entry point to the end of the load module.
+pub fn load_exe(file: &[u8], segment: u16, command_tail: &str) -> Result<Image, io::Error> {
+    let header = MzHeader::parse(file)?;
+    let start = segment.wrapping_add((PSP_SIZE / 16) as u16);
+
+    let mut module = file[header.header_size..header.header_size + header.image_size].to_vec();
+
+    for &(relocation_segment, relocation_offset) in &header.relocations {
+        let offset = ((relocation_segment as usize) << 4) + relocation_offset as usize;
+        let target = module.get_mut(offset..offset + 2).ok_or_else(|| {
+            invalid(format!(
+                "relocation {relocation_segment:04X}:{relocation_offset:04X} is outside the load module"
+            ))
+        })?;
+
+        let value = u16::from_le_bytes([target[0], target[1]]).wrapping_add(start);
+        target.copy_from_slice(&value.to_le_bytes());
+    }
+
+    let mut bytes = psp(command_tail)?;
+    bytes.extend_from_slice(&module);
+    bytes.resize(bytes.len() + header.min_alloc, 0);
+    check_fits(&bytes, segment)?;
+
+    let cs = start.wrapping_add(header.cs);
+    let entry = header.ip as usize;
+    let module_end =
+        (header.image_size + PSP_SIZE).saturating_sub((cs.wrapping_sub(segment) as usize) << 4);
+    let code = entry..module_end.max(entry);
+
+    Ok(Image {
+        bytes,
+        segment,
+        code: vec![code],
+        data: vec![],
+        cs,
+        ip: header.ip,
+        ss: start.wrapping_add(header.ss),
+        sp: header.sp,
+        ds: segment,
+        es: segment,
+    })
+}
+
+fn check_fits(bytes: &[u8], segment: u16) -> Result<(), io::Error> {
+    if physical(segment, 0) + bytes.len() > MEMORY_SIZE {
+        return Err(invalid(format!(
+            "{} bytes loaded at segment {segment:04X} run past the end of memory",
+            bytes.len()
+        )));
+    }
+
+    Ok(())
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn com_places_psp_and_image_in_one_segment() {
+        let image = load_com(&[0xC3], 0x1000, "hi").unwrap();
+
+        assert_eq!(image.bytes.len(), PSP_SIZE + 1);
+        assert_eq!(&image.bytes[0x80..0x84], &[2, b'h', b'i', 0x0D]);
+        assert_eq!(
+            (image.cs, image.ip, image.ss, image.sp),
+            (0x1000, 0x100, 0x1000, 0xFFFE)
+        );
+    }
+
+    #[test]
+    fn com_past_the_end_of_memory_is_rejected() {
+        let err = load_com(&[0x90; 0x100], 0xFFFF, "").err().unwrap();
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    /// An MZ file with `module` as its load module, SS:SP at 0020:0100, CS:IP
+    /// at 0000:0000 and the given relocations.
+    fn mz(module: &[u8], relocations: &[(u16, u16)]) -> Vec<u8> {
+        let header_size = (0x1C + relocations.len() * 4).next_multiple_of(16);
+        let file_size = header_size + module.len();
+
+        let mut file = vec![0; header_size];
+        let mut word = |offset: usize, value: usize| {
+            file[offset..offset + 2].copy_from_slice(&(value as u16).to_le_bytes());
+        };
+        word(0x02, file_size % 512);
+        word(0x04, file_size.div_ceil(512));
+        word(0x06, relocations.len());
+        word(0x08, header_size / 16);
+        word(0x0E, 0x20);
+        word(0x10, 0x100);
+        word(0x18, 0x1C);
+        for (index, (segment, offset)) in relocations.iter().enumerate() {
+            word(0x1C + index * 4, *offset as usize);
+            word(0x1E + index * 4, *segment as usize);
+        }
+
+        file[0..2].copy_from_slice(b"MZ");
+        file.extend_from_slice(module);
+        file
+    }
+
+    #[test]
+    fn mz_header_sizes_are_converted_to_bytes() {
+        let header = MzHeader::parse(&mz(&[0; 600], &[(0, 2), (1, 4)])).unwrap();
+
+        assert_eq!(header.header_size, 0x30);
+        assert_eq!(header.image_size, 600);
+        assert_eq!((header.ss, header.sp), (0x20, 0x100));
+        assert_eq!(header.relocations, vec![(0, 2), (1, 4)]);
+    }
+
+    #[test]
+    fn exe_relocations_are_adjusted_by_the_start_segment() {
+        // `mov ax, 2` whose immediate is a segment, and another at 0001:0002.
+        let mut module = vec![0xB8, 0x02, 0x00, 0xC3];
+        module.resize(0x14, 0);
+        module[0x12..0x14].copy_from_slice(&0x0001u16.to_le_bytes());
+
+        let image = load_exe(&mz(&module, &[(0, 1), (1, 2)]), 0x1000, "").unwrap();
+        let loaded = &image.bytes[PSP_SIZE..];
+
+        assert_eq!(&loaded[1..3], &0x1012u16.to_le_bytes());
+        assert_eq!(&loaded[0x12..0x14], &0x1011u16.to_le_bytes());
+        assert_eq!((image.cs, image.ip), (0x1010, 0));
+        assert_eq!((image.ss, image.sp), (0x1030, 0x100));
+        assert_eq!(image.ds, 0x1000);
+    }
+
+    #[test]
+    fn relocation_outside_the_module_is_rejected() {
+        let err = load_exe(&mz(&[0; 4], &[(0, 3)]), 0x1000, "").err().unwrap();
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn malformed_mz_headers_are_rejected() {
+        let file = mz(&[0; 16], &[]);
+
+        let mut unsigned = file.clone();
+        unsigned[0..2].copy_from_slice(b"XX");
+        assert!(MzHeader::parse(&unsigned).is_err());
+
+        // The header claims more bytes than the file has.
+        assert!(MzHeader::parse(&file[..file.len() - 1]).is_err());
+        assert!(MzHeader::parse(&file[..0x10]).is_err());
+    }
+
+    #[test]
+    fn overlong_command_tail_is_rejected() {
+        assert!(psp(&"x".repeat(127)).is_err());
+        assert!(psp(&"x".repeat(126)).is_ok());
+    }
+}
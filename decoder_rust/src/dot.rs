@@ -0,0 +1,104 @@
+use std::fmt::{self, Display};
+
+use crate::{
+    cfg::{Cfg, EdgeKind},
+    format::FormatOptions,
+};
+
+/// A control-flow graph as a Graphviz digraph: one box per basic block holding
+/// its disassembly, and `taken`/`not taken` on the edges out of conditional
+/// branches. Render with `dot -Tsvg`.
+pub struct Dot<'a> {
+    pub cfg: &'a Cfg,
+    pub options: FormatOptions,
+}
+
+impl<'a> Dot<'a> {
+    pub fn new(cfg: &'a Cfg, options: FormatOptions) -> Self {
+        Self { cfg, options }
+    }
+}
+
+impl Display for Dot<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cfg = self.cfg;
+
+        writeln!(f, "digraph cfg {{")?;
+        writeln!(f, "    node [shape=box, fontname=\"monospace\"];")?;
+
+        for (index, block) in cfg.blocks.iter().enumerate() {
+            let label: String = cfg
+                .instructions(index)
+                .iter()
+                .map(|decoded| {
+                    // Name targets after the blocks they start, as the nodes are.
+                    let target = decoded
+                        .jump_target()
+                        .filter(|target| cfg.block_at(*target).is_some())
+                        .map(|target| format!("L{target:04X}"));
+                    let instruction = decoded
+                        .instruction
+                        .formatted(&self.options)
+                        .with_label(target.as_deref())
+                        .to_string();
+                    // `\l` ends a left-justified line in a Graphviz label.
+                    format!("{:04X}: {}\\l", decoded.offset, escape(&instruction))
+                })
+                .collect();
+
+            writeln!(f, "    L{:04X} [label=\"{label}\"];", block.start)?;
+        }
+
+        for (index, block) in cfg.blocks.iter().enumerate() {
+            let conditional = cfg.successors(index).count() > 1;
+
+            for edge in cfg.successors(index) {
+                let label = match (conditional, edge.kind) {
+                    (false, _) => String::new(),
+                    (true, EdgeKind::Taken) => " [label=\"taken\"]".to_string(),
+                    (true, EdgeKind::Fallthrough) => " [label=\"not taken\"]".to_string(),
+                };
+
+                writeln!(
+                    f,
+                    "    L{:04X} -> L{:04X}{label};",
+                    block.start, cfg.blocks[edge.to].start
+                )?;
+            }
+        }
+
+        write!(f, "}}")
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode_with_offsets;
+
+    #[test]
+    fn conditional_edges_are_labelled_and_targets_named_after_blocks() {
+        // mov cx, 3; add ax, cx; sub cx, 1; jne 3; ret
+        let bytes = [
+            0xB9, 0x03, 0x00, 0x01, 0xC8, 0x83, 0xE9, 0x01, 0x75, 0xF9, 0xC3,
+        ];
+        let cfg = Cfg::new(decode_with_offsets(&bytes));
+
+        assert_eq!(
+            Dot::new(&cfg, FormatOptions::default()).to_string(),
+            r#"digraph cfg {
+    node [shape=box, fontname="monospace"];
+    L0000 [label="0000: mov cx, 3\l"];
+    L0003 [label="0003: add ax, cx\l0005: sub cx, 1\l0008: jne L0003\l"];
+    L000A [label="000A: ret\l"];
+    L0000 -> L0003;
+    L0003 -> L0003 [label="taken"];
+    L0003 -> L000A [label="not taken"];
+}"#
+        );
+    }
+}
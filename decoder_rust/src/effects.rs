@@ -0,0 +1,304 @@
+use std::fmt::{self, Display};
+
+use crate::instruction::{Instruction, Location, Operands, Register};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Flag {
+    Carry,
+    Parity,
+    Auxiliary,
+    Zero,
+    Sign,
+    Overflow,
+}
+
+/// The flags add, sub, cmp, xor and test set, in the order [`crate::sim::Flags`] prints them.
+pub const ARITHMETIC_FLAGS: [Flag; 6] = [
+    Flag::Carry,
+    Flag::Parity,
+    Flag::Auxiliary,
+    Flag::Zero,
+    Flag::Sign,
+    Flag::Overflow,
+];
+
+impl Display for Flag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let letter = match self {
+            Flag::Carry => 'C',
+            Flag::Parity => 'P',
+            Flag::Auxiliary => 'A',
+            Flag::Zero => 'Z',
+            Flag::Sign => 'S',
+            Flag::Overflow => 'O',
+        };
+        write!(f, "{letter}")
+    }
+}
+
+/// What one side of an instruction touches: registers as named, so `al` rather
+/// than `ax`, the flags, and whether it goes through memory at all.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Access {
+    pub registers: Vec<Register>,
+    pub flags: Vec<Flag>,
+    pub memory: bool,
+}
+
+impl Access {
+    fn register(&mut self, register: Register) {
+        if !self.registers.contains(&register) {
+            self.registers.push(register);
+        }
+    }
+
+    /// The registers an operand needs to find its value: the register itself, or
+    /// the base and index of a memory operand.
+    fn address(&mut self, location: &Location) {
+        if !location.is_mem_addr {
+            return;
+        }
+        if let Some(base) = location.register {
+            self.register(base);
+        }
+        if let Some(index) = location.addr_calc {
+            self.register(index);
+        }
+    }
+
+    fn value(&mut self, location: &Location) {
+        match location.register {
+            Some(register) if !location.is_mem_addr => self.register(register),
+            _ => {
+                self.address(location);
+                self.memory = true;
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.registers.is_empty() && self.flags.is_empty() && !self.memory
+    }
+}
+
+impl Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts: Vec<String> = self.registers.iter().map(Register::to_string).collect();
+        if self.memory {
+            parts.push("memory".to_string());
+        }
+        if !self.flags.is_empty() {
+            let flags: String = self.flags.iter().map(Flag::to_string).collect();
+            parts.push(format!("flags {flags}"));
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// Everything `instruction` reads: source operands, a destination it combines
+/// with, registers that address memory, flags a branch tests, and implicit
+/// operands like CX for `loop` and SP for the stack instructions. `xor` of a
+/// register with itself reads nothing, since the result is zero whatever the
+/// register held. Unknown instructions report nothing.
+pub fn reads(instruction: &Instruction) -> Access {
+    let mut access = Access::default();
+
+    match instruction.operands() {
+        Operands::Locations { src, dest } => match instruction {
+            Instruction::Xor { .. } if is_same_register(src, dest) => {}
+            Instruction::Mov { .. } => {
+                access.value(src);
+                access.address(dest);
+            }
+            _ => {
+                access.value(src);
+                access.value(dest);
+            }
+        },
+        Operands::Immediate { dest, .. } => match instruction {
+            Instruction::MovImmediate { .. } => access.address(dest),
+            _ => access.value(dest),
+        },
+        Operands::Stack { location } => {
+            access.register(Register::SP);
+            match instruction {
+                Instruction::Push { .. } => access.value(location),
+                _ => {
+                    access.address(location);
+                    access.memory = true;
+                }
+            }
+        }
+        Operands::Unary { dest, .. } => access.value(dest),
+        Operands::Jump { .. } => {
+            access.flags = branch_flags(instruction).to_vec();
+            match instruction {
+                Instruction::Loop { .. }
+                | Instruction::Loopz { .. }
+                | Instruction::Loopnz { .. }
+                | Instruction::Jcxz { .. } => access.register(Register::CX),
+                Instruction::Call { .. } => access.register(Register::SP),
+                _ => {}
+            }
+        }
+        Operands::None => {
+            if let Instruction::Ret = instruction {
+                access.register(Register::SP);
+                access.memory = true;
+            }
+        }
+    }
+
+    access
+}
+
+/// Everything `instruction` writes: its destination unless it only compares or
+/// tests, the arithmetic flags (all but carry for `inc` and `dec`), CX for the
+/// `loop` family, and SP and the stack for `push`, `pop`, `call` and `ret`.
+pub fn writes(instruction: &Instruction) -> Access {
+    let mut access = Access::default();
+
+    match instruction {
+        Instruction::Mov { dest, .. } | Instruction::MovImmediate { dest, .. } => {
+            write_to(&mut access, dest);
+        }
+        Instruction::Add { dest, .. }
+        | Instruction::AddImmediate { dest, .. }
+        | Instruction::Sub { dest, .. }
+        | Instruction::SubImmediate { dest, .. }
+        | Instruction::Xor { dest, .. }
+        | Instruction::XorImmediate { dest, .. } => {
+            write_to(&mut access, dest);
+            access.flags = ARITHMETIC_FLAGS.to_vec();
+        }
+        Instruction::Cmp { .. }
+        | Instruction::CmpImmediate { .. }
+        | Instruction::Test { .. }
+        | Instruction::TestImmediate { .. } => {
+            access.flags = ARITHMETIC_FLAGS.to_vec();
+        }
+        Instruction::Inc { dest, .. } | Instruction::Dec { dest, .. } => {
+            write_to(&mut access, dest);
+            access.flags = ARITHMETIC_FLAGS
+                .into_iter()
+                .filter(|flag| *flag != Flag::Carry)
+                .collect();
+        }
+        Instruction::Push { .. } => {
+            access.register(Register::SP);
+            access.memory = true;
+        }
+        Instruction::Pop { dest } => {
+            write_to(&mut access, dest);
+            access.register(Register::SP);
+        }
+        Instruction::Loop { .. } | Instruction::Loopz { .. } | Instruction::Loopnz { .. } => {
+            access.register(Register::CX);
+        }
+        Instruction::Call { .. } => {
+            access.register(Register::SP);
+            access.memory = true;
+        }
+        Instruction::Ret => access.register(Register::SP),
+        _ => {}
+    }
+
+    access
+}
+
+fn is_same_register(a: &Location, b: &Location) -> bool {
+    !a.is_mem_addr && !b.is_mem_addr && a.register.is_some() && a.register == b.register
+}
+
+fn write_to(access: &mut Access, dest: &Location) {
+    match dest.register {
+        Some(register) if !dest.is_mem_addr => access.register(register),
+        _ => access.memory = true,
+    }
+}
+
+/// The flags a conditional jump or `loopz`/`loopnz` tests.
+pub fn branch_flags(instruction: &Instruction) -> &'static [Flag] {
+    match instruction {
+        Instruction::Je { .. } | Instruction::Jne { .. } => &[Flag::Zero],
+        Instruction::Jl { .. } | Instruction::Jnl { .. } => &[Flag::Sign, Flag::Overflow],
+        Instruction::Jle { .. } | Instruction::Jnle { .. } => {
+            &[Flag::Zero, Flag::Sign, Flag::Overflow]
+        }
+        Instruction::Jb { .. } | Instruction::Jnb { .. } => &[Flag::Carry],
+        Instruction::Jbe { .. } | Instruction::Jnbe { .. } => &[Flag::Carry, Flag::Zero],
+        Instruction::Jp { .. } | Instruction::Jnp { .. } => &[Flag::Parity],
+        Instruction::Jo { .. } | Instruction::Jno { .. } => &[Flag::Overflow],
+        Instruction::Js { .. } | Instruction::Jns { .. } => &[Flag::Sign],
+        Instruction::Loopz { .. } | Instruction::Loopnz { .. } => &[Flag::Zero],
+        _ => &[],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode_with_offsets;
+
+    fn effects(bytes: &[u8]) -> (Access, Access) {
+        let decoded = decode_with_offsets(bytes);
+        assert_eq!(decoded.len(), 1, "expected a single instruction");
+        let instruction = &decoded[0].instruction;
+        (reads(instruction), writes(instruction))
+    }
+
+    #[test]
+    fn memory_destination_is_read_through_its_address() {
+        // add [bx + si], ax
+        let (reads, writes) = effects(&[0x01, 0x00]);
+
+        assert_eq!(
+            reads.registers,
+            vec![Register::AX, Register::BX, Register::SI]
+        );
+        assert!(reads.memory);
+        assert!(writes.registers.is_empty());
+        assert!(writes.memory);
+        assert_eq!(writes.flags, ARITHMETIC_FLAGS);
+    }
+
+    #[test]
+    fn mov_only_reads_what_it_needs() {
+        // mov al, [bx]
+        let (reads, writes) = effects(&[0x8A, 0x07]);
+
+        assert_eq!(reads.registers, vec![Register::BX]);
+        assert!(reads.memory);
+        assert_eq!(writes.registers, vec![Register::AL]);
+        assert!(writes.flags.is_empty());
+    }
+
+    #[test]
+    fn cmp_writes_only_flags() {
+        // cmp ax, bx
+        let (reads, writes) = effects(&[0x39, 0xD8]);
+
+        assert_eq!(reads.registers, vec![Register::BX, Register::AX]);
+        assert!(writes.registers.is_empty() && !writes.memory);
+        assert_eq!(writes.flags, ARITHMETIC_FLAGS);
+    }
+
+    #[test]
+    fn implicit_operands() {
+        // loop -2
+        let (reads, writes) = effects(&[0xE2, 0xFE]);
+        assert_eq!(reads.registers, vec![Register::CX]);
+        assert_eq!(writes.registers, vec![Register::CX]);
+
+        // jne -2
+        let (reads, writes) = effects(&[0x75, 0xFE]);
+        assert_eq!(reads.flags, vec![Flag::Zero]);
+        assert!(writes.is_empty());
+
+        // push ax
+        let (reads, writes) = effects(&[0x50]);
+        assert_eq!(reads.registers, vec![Register::SP, Register::AX]);
+        assert_eq!(writes.registers, vec![Register::SP]);
+        assert!(writes.memory);
+    }
+}
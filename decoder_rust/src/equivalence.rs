@@ -0,0 +1,458 @@
+use std::fmt::{self, Display};
+
+use crate::{
+    instruction::{Instruction, Register},
+    sim::{Cpu, Flags, MEMORY_SIZE},
+    symbolic::State,
+    Decoded,
+};
+
+const WORD_REGISTERS: [Register; 8] = [
+    Register::AX,
+    Register::CX,
+    Register::DX,
+    Register::BX,
+    Register::SP,
+    Register::BP,
+    Register::SI,
+    Register::DI,
+];
+
+/// Register values tried before the random ones, where carries and sign
+/// changes tend to show up.
+const EDGE_VALUES: [u16; 4] = [0x0000, 0xFFFF, 0x8000, 0x7FFF];
+
+#[derive(Debug, Clone, Copy)]
+pub struct CheckOptions {
+    /// Whether the flags left behind have to match too.
+    pub flags: bool,
+    /// How many starting states to run both sequences from when their symbolic
+    /// states don't settle it.
+    pub trials: usize,
+    /// Instructions to run before giving up on a sequence that doesn't finish.
+    pub max_steps: usize,
+}
+
+impl Default for CheckOptions {
+    fn default() -> Self {
+        Self {
+            flags: true,
+            trials: 256,
+            max_steps: 10_000,
+        }
+    }
+}
+
+/// Which of the two sequences something is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    First,
+    Second,
+}
+
+impl Display for Side {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Side::First => write!(f, "first"),
+            Side::Second => write!(f, "second"),
+        }
+    }
+}
+
+/// Why two sequences couldn't be compared at all.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// An instruction the decoder doesn't know, whose effects can't be modelled.
+    Undecoded { side: Side, offset: usize },
+    /// A sequence still running after [`CheckOptions::max_steps`].
+    Unfinished { side: Side, steps: usize },
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::Undecoded { side, offset } => {
+                write!(
+                    f,
+                    "{side} sequence has an undecoded instruction at {offset:04X}"
+                )
+            }
+            Problem::Unfinished { side, steps } => {
+                write!(f, "{side} sequence didn't finish within {steps} steps")
+            }
+        }
+    }
+}
+
+/// Where control went once a sequence finished.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exit {
+    /// Past its last instruction.
+    End,
+    /// Somewhere outside it, by a jump, call or return.
+    Jump(u16),
+}
+
+impl Display for Exit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Exit::End => write!(f, "the end"),
+            Exit::Jump(ip) => write!(f, "{ip:04X}"),
+        }
+    }
+}
+
+/// The first thing found to differ after running both sequences.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Difference {
+    Register(Register, u16, u16),
+    Memory(usize, u8, u8),
+    Flags(Flags, Flags),
+    Exit(Exit, Exit),
+}
+
+impl Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Difference::Register(register, first, second) => {
+                write!(f, "{register} is {first:#06x} vs {second:#06x}")
+            }
+            Difference::Memory(address, first, second) => {
+                write!(f, "byte at {address:05X} is {first:#04x} vs {second:#04x}")
+            }
+            Difference::Flags(first, second) => {
+                write!(f, "flags are [{first}] vs [{second}]")
+            }
+            Difference::Exit(first, second) => {
+                write!(f, "control leaves to {first} vs {second}")
+            }
+        }
+    }
+}
+
+/// A starting state the sequences disagree on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Counterexample {
+    pub registers: Vec<(Register, u16)>,
+    pub flags: Flags,
+    pub difference: Difference,
+}
+
+impl Display for Counterexample {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let registers: Vec<String> = self
+            .registers
+            .iter()
+            .map(|(register, value)| format!("{register}={value:#06x}"))
+            .collect();
+        write!(
+            f,
+            "{} from {} flags [{}]",
+            self.difference,
+            registers.join(" "),
+            self.flags
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    /// Both leave the same expressions everywhere, so they agree on every input.
+    Proven,
+    /// No difference in any of the trials, which is evidence rather than proof.
+    Agreed {
+        trials: usize,
+    },
+    Differs(Counterexample),
+}
+
+impl Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Verdict::Proven => write!(f, "equivalent: both compute the same expressions"),
+            Verdict::Agreed { trials } => {
+                write!(f, "equivalent on all {trials} starting states tried")
+            }
+            Verdict::Differs(counterexample) => write!(f, "not equivalent: {counterexample}"),
+        }
+    }
+}
+
+/// Decides whether `first` and `second`, each decoded from offset 0, leave the
+/// same registers, memory and, unless told otherwise, flags. Straight-line
+/// sequences are compared symbolically first; anything that symbolic evaluation
+/// can't settle, including code with branches, is run from the same starting
+/// states on the simulator. Segments are all zero, so DS and SS coincide.
+pub fn check(
+    first: &[Decoded],
+    second: &[Decoded],
+    options: CheckOptions,
+) -> Result<Verdict, Problem> {
+    for (side, lines) in [(Side::First, first), (Side::Second, second)] {
+        if let Some(decoded) = lines
+            .iter()
+            .find(|decoded| matches!(decoded.instruction, Instruction::Noop))
+        {
+            return Err(Problem::Undecoded {
+                side,
+                offset: decoded.offset,
+            });
+        }
+    }
+
+    if is_straight_line(first) && is_straight_line(second) {
+        let summary = |lines: &[Decoded]| {
+            State::run(lines).ok().map(|mut state| {
+                if !options.flags {
+                    state.flags = None;
+                }
+                state
+            })
+        };
+        if let (Some(a), Some(b)) = (summary(first), summary(second)) {
+            if a == b {
+                return Ok(Verdict::Proven);
+            }
+        }
+    }
+
+    let mut random = XorShift(0x2545_F491_4F6C_DD1D);
+    let mut start = Cpu::new();
+    for chunk in start.memory.chunks_mut(8) {
+        chunk.copy_from_slice(&random.next().to_le_bytes()[..chunk.len()]);
+    }
+
+    for trial in 0..options.trials {
+        for register in WORD_REGISTERS {
+            let value = match EDGE_VALUES.get(trial) {
+                Some(value) => *value,
+                None => random.next() as u16,
+            };
+            start.set_register(register, value);
+        }
+        start.flags = random_flags(random.next());
+
+        let (a, exit_a) = run(&start, first, options.max_steps).ok_or(Problem::Unfinished {
+            side: Side::First,
+            steps: options.max_steps,
+        })?;
+        let (b, exit_b) = run(&start, second, options.max_steps).ok_or(Problem::Unfinished {
+            side: Side::Second,
+            steps: options.max_steps,
+        })?;
+
+        if let Some(difference) = compare(&a, exit_a, &b, exit_b, options.flags) {
+            return Ok(Verdict::Differs(Counterexample {
+                registers: WORD_REGISTERS
+                    .iter()
+                    .map(|register| (*register, start.register(*register)))
+                    .collect(),
+                flags: start.flags,
+                difference,
+            }));
+        }
+    }
+
+    Ok(Verdict::Agreed {
+        trials: options.trials,
+    })
+}
+
+/// Whether the sequence runs top to bottom with nothing that could send
+/// control anywhere else.
+fn is_straight_line(lines: &[Decoded]) -> bool {
+    lines
+        .iter()
+        .all(|decoded| decoded.jump_target().is_none() && decoded.instruction.falls_through())
+}
+
+/// Runs `lines` from their first offset until IP lands on none of them.
+fn run(start: &Cpu, lines: &[Decoded], max_steps: usize) -> Option<(Cpu, Exit)> {
+    let mut cpu = start.clone();
+    cpu.ip = 0;
+    let end = lines.last().map_or(0, |last| last.offset + last.size);
+
+    for _ in 0..max_steps {
+        let Some(decoded) = lines
+            .iter()
+            .find(|decoded| decoded.offset == cpu.ip as usize)
+        else {
+            let exit = match cpu.ip as usize == end {
+                true => Exit::End,
+                false => Exit::Jump(cpu.ip),
+            };
+            return Some((cpu, exit));
+        };
+        cpu.execute(decoded);
+    }
+
+    None
+}
+
+fn compare(a: &Cpu, exit_a: Exit, b: &Cpu, exit_b: Exit, flags: bool) -> Option<Difference> {
+    for register in WORD_REGISTERS {
+        let (first, second) = (a.register(register), b.register(register));
+        if first != second {
+            return Some(Difference::Register(register, first, second));
+        }
+    }
+
+    if a.memory != b.memory {
+        let address = (0..MEMORY_SIZE)
+            .find(|address| a.memory[*address] != b.memory[*address])
+            .expect("memory differs somewhere");
+        return Some(Difference::Memory(
+            address,
+            a.memory[address],
+            b.memory[address],
+        ));
+    }
+
+    if flags && a.flags != b.flags {
+        return Some(Difference::Flags(a.flags, b.flags));
+    }
+
+    (exit_a != exit_b).then_some(Difference::Exit(exit_a, exit_b))
+}
+
+fn random_flags(bits: u64) -> Flags {
+    let bit = |n: u32| bits >> n & 1 == 1;
+    Flags {
+        carry: bit(0),
+        parity: bit(1),
+        auxiliary: bit(2),
+        zero: bit(3),
+        sign: bit(4),
+        overflow: bit(5),
+    }
+}
+
+/// Marsaglia's xorshift64, so runs are repeatable without another dependency.
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode_with_offsets;
+
+    fn check_bytes(first: &[u8], second: &[u8], flags: bool) -> Result<Verdict, Problem> {
+        let options = CheckOptions {
+            flags,
+            ..Default::default()
+        };
+        check(
+            &decode_with_offsets(first),
+            &decode_with_offsets(second),
+            options,
+        )
+    }
+
+    #[test]
+    fn matching_expressions_are_proven() {
+        // mov ax, bx; add ax, ax vs mov ax, bx; add ax, bx
+        let verdict = check_bytes(&[0x89, 0xD8, 0x01, 0xC0], &[0x89, 0xD8, 0x01, 0xD8], true);
+
+        assert_eq!(verdict, Ok(Verdict::Proven));
+    }
+
+    #[test]
+    fn a_differing_register_is_a_counterexample() {
+        // add ax, 1 vs add ax, 2
+        let Ok(Verdict::Differs(counterexample)) =
+            check_bytes(&[0x05, 0x01, 0x00], &[0x05, 0x02, 0x00], true)
+        else {
+            panic!("expected a counterexample");
+        };
+
+        assert_eq!(
+            counterexample.difference,
+            Difference::Register(Register::AX, 1, 2)
+        );
+    }
+
+    #[test]
+    fn what_symbolic_evaluation_misses_is_tried() {
+        // xor ax, bx; xor ax, bx vs mov ax, ax
+        let verdict = check_bytes(&[0x31, 0xD8, 0x31, 0xD8], &[0x89, 0xC0], false);
+
+        assert_eq!(verdict, Ok(Verdict::Agreed { trials: 256 }));
+    }
+
+    #[test]
+    fn zeroing_with_xor_only_differs_in_the_flags() {
+        // mov ax, 0 vs xor ax, ax
+        let (mov, xor) = ([0xB8, 0x00, 0x00], [0x31, 0xC0]);
+
+        assert_eq!(check_bytes(&mov, &xor, false), Ok(Verdict::Proven));
+        assert!(matches!(
+            check_bytes(&mov, &xor, true),
+            Ok(Verdict::Differs(Counterexample {
+                difference: Difference::Flags(..),
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn a_byte_store_does_not_fix_the_byte_after_it() {
+        // mov byte [bx], 7; mov al, [bx + 1] vs mov byte [bx], 7; mov al, 0
+        let verdict = check_bytes(
+            &[0xC6, 0x07, 0x07, 0x8A, 0x47, 0x01],
+            &[0xC6, 0x07, 0x07, 0xB0, 0x00],
+            false,
+        );
+
+        assert!(matches!(
+            verdict,
+            Ok(Verdict::Differs(Counterexample {
+                difference: Difference::Register(Register::AX, ..),
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn undecoded_instructions_are_refused() {
+        // mov ax, bx vs mov ax, bx; nop
+        let verdict = check_bytes(&[0x89, 0xD8], &[0x89, 0xD8, 0x90], true);
+
+        assert_eq!(
+            verdict,
+            Err(Problem::Undecoded {
+                side: Side::Second,
+                offset: 2
+            })
+        );
+    }
+
+    #[test]
+    fn sequences_that_never_finish_are_refused() {
+        // jmp $ vs mov ax, bx
+        let options = CheckOptions {
+            max_steps: 100,
+            ..Default::default()
+        };
+        let verdict = check(
+            &decode_with_offsets(&[0xEB, 0xFE]),
+            &decode_with_offsets(&[0x89, 0xD8]),
+            options,
+        );
+
+        assert_eq!(
+            verdict,
+            Err(Problem::Unfinished {
+                side: Side::First,
+                steps: 100
+            })
+        );
+    }
+}
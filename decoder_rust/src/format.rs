@@ -0,0 +1,583 @@
+use std::fmt::{self, Display};
+
+use clap::ValueEnum;
+
+use crate::instruction::{Immediate, Instruction, Location, Operands, Width};
+
+mod att;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Syntax {
+    /// Intel operand order as accepted by nasm, the style the decoder has always printed.
+    #[default]
+    Nasm,
+    /// GNU as `.code16` AT&T syntax, comparable against `objdump -m i8086`.
+    Att,
+    /// MASM/TASM syntax: `word ptr`, `ds:` on direct addresses, `short` jumps and `0FFh` hex.
+    Masm,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum NumberStyle {
+    /// Decimal, with immediates as the signed values they were encoded as.
+    #[default]
+    Signed,
+    /// Decimal, with immediates as unsigned values.
+    Unsigned,
+    /// `0xFF`, or `0FFh` in MASM syntax.
+    Hex,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SizeStyle {
+    /// `word [bx]`, or `word 10` on the immediate as nasm disassemblers print it.
+    #[default]
+    Nasm,
+    /// `word ptr [bx]`, always attached to the memory operand.
+    Masm,
+}
+
+#[derive(Debug, Clone)]
+pub struct FormatOptions {
+    pub syntax: Syntax,
+    pub numbers: NumberStyle,
+    pub uppercase: bool,
+    pub sizes: SizeStyle,
+    /// Size every memory operand, even when a register operand already implies it.
+    pub explicit_sizes: bool,
+    /// Separate the mnemonic from its operands with a tab instead of a space.
+    pub align_operands: bool,
+    /// Write `[bx + si + 4]` rather than `[bx+si+4]`.
+    pub spaced_operators: bool,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            syntax: Syntax::Nasm,
+            numbers: NumberStyle::Signed,
+            uppercase: false,
+            sizes: SizeStyle::Nasm,
+            explicit_sizes: false,
+            align_operands: false,
+            spaced_operators: true,
+        }
+    }
+}
+
+impl FormatOptions {
+    pub(crate) fn keyword(&self, keyword: &str) -> String {
+        if self.uppercase {
+            keyword.to_uppercase()
+        } else {
+            keyword.to_lowercase()
+        }
+    }
+
+    fn size(&self, width: Width) -> String {
+        let sizes = match self.syntax {
+            Syntax::Masm => SizeStyle::Masm,
+            _ => self.sizes,
+        };
+        let size = match (width, sizes) {
+            (Width::Byte, SizeStyle::Nasm) => "byte",
+            (Width::Word, SizeStyle::Nasm) => "word",
+            (Width::Byte, SizeStyle::Masm) => "byte ptr",
+            (Width::Word, SizeStyle::Masm) => "word ptr",
+        };
+        self.keyword(size)
+    }
+
+    fn hex(&self, value: u16) -> String {
+        match self.syntax {
+            Syntax::Masm => {
+                let digits = format!("{value:X}");
+                match digits.starts_with(|digit: char| digit.is_ascii_alphabetic()) {
+                    true => format!("0{digits}h"),
+                    false => format!("{digits}h"),
+                }
+            }
+            _ => format!("0x{value:X}"),
+        }
+    }
+
+    fn signed(&self, value: i16) -> String {
+        match self.numbers {
+            NumberStyle::Hex => self.hex(value.unsigned_abs()),
+            _ => value.unsigned_abs().to_string(),
+        }
+    }
+
+    fn immediate(&self, data: &Immediate) -> String {
+        match (self.numbers, data) {
+            (NumberStyle::Signed, Immediate::Byte(data)) => data.to_string(),
+            (NumberStyle::Signed, Immediate::Word(data)) => data.to_string(),
+            (NumberStyle::Unsigned, Immediate::Byte(data)) => (*data as u8).to_string(),
+            (NumberStyle::Unsigned, Immediate::Word(data)) => (*data as u16).to_string(),
+            (NumberStyle::Hex, Immediate::Byte(data)) => self.hex(*data as u8 as u16),
+            (NumberStyle::Hex, Immediate::Word(data)) => self.hex(*data as u16),
+        }
+    }
+
+    fn address(&self, address: i16) -> String {
+        match self.numbers {
+            NumberStyle::Signed => address.to_string(),
+            NumberStyle::Unsigned => (address as u16).to_string(),
+            NumberStyle::Hex => self.hex(address as u16),
+        }
+    }
+}
+
+pub struct Formatted<'a> {
+    instruction: &'a Instruction,
+    options: &'a FormatOptions,
+    label: Option<&'a str>,
+    symbol: Option<&'a str>,
+}
+
+impl<'a> Formatted<'a> {
+    pub fn new(instruction: &'a Instruction, options: &'a FormatOptions) -> Self {
+        Self {
+            instruction,
+            options,
+            label: None,
+            symbol: None,
+        }
+    }
+
+    /// Names the jump target by `label` instead of by its relative increment.
+    pub fn with_label(mut self, label: Option<&'a str>) -> Self {
+        self.label = label;
+        self
+    }
+
+    /// Writes the displacement of a register-based memory operand as `symbol`,
+    /// which the output has to define as that displacement.
+    pub fn with_symbol(mut self, symbol: Option<&'a str>) -> Self {
+        self.symbol = symbol;
+        self
+    }
+}
+
+impl Display for Formatted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.options.syntax {
+            Syntax::Nasm | Syntax::Masm => self.fmt_intel(f),
+            Syntax::Att => {
+                att::write_instruction(f, self.instruction, self.options, self.label, self.symbol)
+            }
+        }
+    }
+}
+
+impl Formatted<'_> {
+    fn fmt_intel(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let options = self.options;
+
+        write!(f, "{}", options.keyword(self.instruction.mnemonic()))?;
+
+        let separator = if options.align_operands { "\t" } else { " " };
+
+        match self.instruction.operands() {
+            Operands::Locations { src, dest } => {
+                let width = dest.width().or(src.width());
+                let sized = |location: &Location| {
+                    width.filter(|_| options.explicit_sizes && location.is_mem_addr)
+                };
+
+                write!(f, "{separator}")?;
+                write_location(f, dest, options, sized(dest), self.symbol)?;
+                write!(f, ", ")?;
+                write_location(f, src, options, sized(src), self.symbol)
+            }
+            Operands::Immediate { data, dest } => {
+                let on_location = dest.is_mem_addr
+                    && (options.explicit_sizes
+                        || options.sizes == SizeStyle::Masm
+                        || options.syntax == Syntax::Masm);
+                let on_immediate = dest.is_mem_addr && !on_location;
+
+                write!(f, "{separator}")?;
+                write_location(
+                    f,
+                    dest,
+                    options,
+                    on_location.then(|| data.width()),
+                    self.symbol,
+                )?;
+                write!(f, ", ")?;
+                if on_immediate {
+                    write!(f, "{} ", options.size(data.width()))?;
+                }
+                write!(f, "{}", options.immediate(data))
+            }
+            Operands::Stack { location } => {
+                write!(f, "{separator}")?;
+                let size = Some(Width::Word).filter(|_| location.is_mem_addr);
+                write_location(f, location, options, size, self.symbol)
+            }
+            Operands::Unary { width, dest } => {
+                write!(f, "{separator}")?;
+                let size = Some(width).filter(|_| dest.is_mem_addr);
+                write_location(f, dest, options, size, self.symbol)
+            }
+            Operands::Jump { increment } => {
+                let (increment, length) = match increment {
+                    Immediate::Byte(increment) => (*increment as i16, 2),
+                    Immediate::Word(increment) => (*increment, 3),
+                };
+
+                write!(f, "{separator}")?;
+
+                // Left to choose, an assembler would shrink a near jmp whose target fits in a byte.
+                if length == 3 && matches!(self.instruction, Instruction::Jmp { .. }) {
+                    let near = match options.syntax {
+                        Syntax::Masm => "near ptr",
+                        _ => "near",
+                    };
+                    write!(f, "{} ", options.keyword(near))?;
+                }
+
+                if options.syntax == Syntax::Masm {
+                    // Loops and jcxz only come in the short form, so MASM rejects the override.
+                    if length == 2 && !self.instruction.is_loop() {
+                        write!(f, "{} ", options.keyword("short"))?;
+                    }
+
+                    if let Some(label) = self.label {
+                        return write!(f, "{label}");
+                    }
+
+                    // `$` is the start of the jump, not the end the increment counts from.
+                    let relative = increment.wrapping_add(length);
+                    let sign = if relative < 0 { "-" } else { "+" };
+                    return write!(f, "${sign}{}", options.signed(relative));
+                }
+
+                if let Some(label) = self.label {
+                    return write!(f, "{label}");
+                }
+
+                let sign = if increment < 0 { "-" } else { "" };
+                write!(f, "{sign}{}", options.signed(increment))
+            }
+            Operands::None => Ok(()),
+        }
+    }
+}
+
+/// Data bytes as the assembler's define-byte directive, with zero padding
+/// collapsed into a repeat count.
+pub struct FormattedData<'a> {
+    bytes: &'a [u8],
+    options: &'a FormatOptions,
+}
+
+impl<'a> FormattedData<'a> {
+    pub fn new(bytes: &'a [u8], options: &'a FormatOptions) -> Self {
+        Self { bytes, options }
+    }
+}
+
+impl Display for FormattedData<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let options = self.options;
+        let count = self.bytes.len();
+        let separator = if options.align_operands { "\t" } else { " " };
+
+        if count > 1 && self.bytes.iter().all(|byte| *byte == 0) {
+            return match options.syntax {
+                Syntax::Nasm => write!(
+                    f,
+                    "{} {count} {}{separator}0",
+                    options.keyword("times"),
+                    options.keyword("db")
+                ),
+                Syntax::Masm => write!(
+                    f,
+                    "{}{separator}{count} {} (0)",
+                    options.keyword("db"),
+                    options.keyword("dup")
+                ),
+                Syntax::Att => write!(f, "{}{separator}{count}, 1, 0", options.keyword(".fill")),
+            };
+        }
+
+        let directive = match options.syntax {
+            Syntax::Nasm | Syntax::Masm => "db",
+            Syntax::Att => ".byte",
+        };
+        let values = self
+            .bytes
+            .iter()
+            .map(|byte| match options.numbers {
+                NumberStyle::Signed | NumberStyle::Unsigned => byte.to_string(),
+                NumberStyle::Hex => options.hex(*byte as u16),
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        write!(f, "{}{separator}{values}", options.keyword(directive))
+    }
+}
+
+pub(crate) fn write_location(
+    f: &mut fmt::Formatter<'_>,
+    location: &Location,
+    options: &FormatOptions,
+    size: Option<Width>,
+    symbol: Option<&str>,
+) -> fmt::Result {
+    let Location {
+        ref register,
+        is_mem_addr,
+        ref addr_calc,
+        ref displacement,
+    } = *location;
+
+    if let Some(size) = size {
+        write!(f, "{} ", options.size(size))?;
+    }
+
+    let (plus, minus) = if options.spaced_operators {
+        (" + ", " - ")
+    } else {
+        ("+", "-")
+    };
+
+    if let Some(register) = register {
+        if is_mem_addr {
+            let mut msg = options.keyword(&register.to_string());
+            if let Some(addr_calc) = addr_calc {
+                msg.push_str(plus);
+                msg.push_str(&options.keyword(&addr_calc.to_string()));
+            }
+            if let Some(symbol) = symbol {
+                msg.push_str(plus);
+                msg.push_str(symbol);
+            } else if let Some(displacement) = displacement {
+                if *displacement < 0 {
+                    msg.push_str(minus);
+                } else if *displacement > 0 {
+                    msg.push_str(plus);
+                }
+                if *displacement != 0 {
+                    msg.push_str(&options.signed(*displacement));
+                }
+            }
+            write!(f, "[{}]", msg)
+        } else {
+            write!(f, "{}", options.keyword(&register.to_string()))
+        }
+    } else {
+        let direct_address = displacement.expect("displacement required for direct address");
+        if options.syntax == Syntax::Masm {
+            // MASM reads a bare `[16]` as the constant 16, so name the segment.
+            write!(f, "{}:", options.keyword("ds"))?;
+        }
+        write!(f, "[{}]", options.address(direct_address))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode_with_offsets, instruction::Register};
+
+    /// mov ax, [bp + si - 4]; add ax, -1; mov word [4660], 5; dec byte [bx]; jne -6;
+    /// loop -2; jmp near 256
+    const SAMPLE: [u8; 21] = [
+        0x8B, 0x42, 0xFC, 0x83, 0xC0, 0xFF, 0xC7, 0x06, 0x34, 0x12, 0x05, 0x00, 0xFE, 0x0F, 0x75,
+        0xFA, 0xE2, 0xFE, 0xE9, 0x00, 0x01,
+    ];
+
+    fn formatted(options: FormatOptions) -> Vec<String> {
+        decode_with_offsets(&SAMPLE)
+            .iter()
+            .map(|decoded| decoded.instruction.formatted(&options).to_string())
+            .collect()
+    }
+
+    fn with_syntax(syntax: Syntax) -> FormatOptions {
+        FormatOptions {
+            syntax,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn nasm_is_the_default() {
+        assert_eq!(
+            formatted(FormatOptions::default()),
+            [
+                "mov ax, [bp + si - 4]",
+                "add ax, -1",
+                "mov [4660], word 5",
+                "dec byte [bx]",
+                "jne -6",
+                "loop -2",
+                "jmp near 256",
+            ]
+        );
+    }
+
+    #[test]
+    fn number_styles() {
+        let numbers = |numbers| {
+            formatted(FormatOptions {
+                numbers,
+                ..Default::default()
+            })[..3]
+                .to_vec()
+        };
+
+        assert_eq!(
+            numbers(NumberStyle::Unsigned),
+            [
+                "mov ax, [bp + si - 4]",
+                "add ax, 65535",
+                "mov [4660], word 5"
+            ]
+        );
+        assert_eq!(
+            numbers(NumberStyle::Hex),
+            [
+                "mov ax, [bp + si - 0x4]",
+                "add ax, 0xFFFF",
+                "mov [0x1234], word 0x5"
+            ]
+        );
+    }
+
+    #[test]
+    fn case_sizes_and_spacing() {
+        let first = |options| formatted(options)[..4].to_vec();
+
+        assert_eq!(
+            first(FormatOptions {
+                uppercase: true,
+                ..Default::default()
+            }),
+            [
+                "MOV AX, [BP + SI - 4]",
+                "ADD AX, -1",
+                "MOV [4660], WORD 5",
+                "DEC BYTE [BX]"
+            ]
+        );
+        assert_eq!(
+            first(FormatOptions {
+                sizes: SizeStyle::Masm,
+                ..Default::default()
+            }),
+            [
+                "mov ax, [bp + si - 4]",
+                "add ax, -1",
+                "mov word ptr [4660], 5",
+                "dec byte ptr [bx]"
+            ]
+        );
+        assert_eq!(
+            first(FormatOptions {
+                explicit_sizes: true,
+                ..Default::default()
+            }),
+            [
+                "mov ax, word [bp + si - 4]",
+                "add ax, -1",
+                "mov word [4660], 5",
+                "dec byte [bx]"
+            ]
+        );
+        assert_eq!(
+            first(FormatOptions {
+                align_operands: true,
+                spaced_operators: false,
+                ..Default::default()
+            }),
+            [
+                "mov\tax, [bp+si-4]",
+                "add\tax, -1",
+                "mov\t[4660], word 5",
+                "dec\tbyte [bx]"
+            ]
+        );
+    }
+
+    #[test]
+    fn att_puts_the_source_first_and_sizes_the_mnemonic() {
+        assert_eq!(
+            formatted(with_syntax(Syntax::Att)),
+            [
+                "movw -4(%bp,%si), %ax",
+                "addw $-1, %ax",
+                "movw $5, 4660",
+                "decb (%bx)",
+                "jne .-4",
+                "loop .+0",
+                "jmp .+259",
+            ]
+        );
+    }
+
+    #[test]
+    fn att_sizes_pushes_and_pops_of_memory() {
+        let memory = || Location {
+            is_mem_addr: true,
+            register: Some(Register::BX),
+            addr_calc: None,
+            displacement: None,
+        };
+        let options = with_syntax(Syntax::Att);
+
+        let push = Instruction::Push { src: memory() };
+        let pop = Instruction::Pop { dest: memory() };
+        assert_eq!(push.formatted(&options).to_string(), "pushw (%bx)");
+        assert_eq!(pop.formatted(&options).to_string(), "popw (%bx)");
+    }
+
+    #[test]
+    fn masm_sizes_with_ptr_names_the_segment_and_jumps_from_dollar() {
+        assert_eq!(
+            formatted(with_syntax(Syntax::Masm)),
+            [
+                "mov ax, [bp + si - 4]",
+                "add ax, -1",
+                "mov word ptr ds:[4660], 5",
+                "dec byte ptr [bx]",
+                "jne short $-4",
+                "loop $+0",
+                "jmp near ptr $+259",
+            ]
+        );
+    }
+
+    #[test]
+    fn masm_hex_leads_with_a_digit() {
+        let options = FormatOptions {
+            syntax: Syntax::Masm,
+            numbers: NumberStyle::Hex,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            formatted(options)[..3],
+            [
+                "mov ax, [bp + si - 4h]",
+                "add ax, 0FFFFh",
+                "mov word ptr ds:[1234h], 5h"
+            ]
+        );
+    }
+
+    #[test]
+    fn zero_padding_becomes_a_repeat_in_every_syntax() {
+        let data =
+            |syntax, bytes: &[u8]| FormattedData::new(bytes, &with_syntax(syntax)).to_string();
+
+        assert_eq!(data(Syntax::Nasm, &[0; 16]), "times 16 db 0");
+        assert_eq!(data(Syntax::Masm, &[0; 16]), "db 16 dup (0)");
+        assert_eq!(data(Syntax::Att, &[0; 16]), ".fill 16, 1, 0");
+        assert_eq!(data(Syntax::Att, &[1, 0, 255]), ".byte 1, 0, 255");
+    }
+}
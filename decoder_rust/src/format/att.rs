@@ -0,0 +1,129 @@
+use std::fmt;
+
+use crate::instruction::{Immediate, Instruction, Location, Operands, Width};
+
+use super::FormatOptions;
+
+/// Renders `instruction` the way GNU as expects it under `.code16`: source
+/// operand first, `%` registers, `$` immediates and a size suffix on the mnemonic.
+pub(super) fn write_instruction(
+    f: &mut fmt::Formatter<'_>,
+    instruction: &Instruction,
+    options: &FormatOptions,
+    label: Option<&str>,
+    symbol: Option<&str>,
+) -> fmt::Result {
+    let mnemonic = instruction.mnemonic();
+    let separator = if options.align_operands { "\t" } else { " " };
+
+    if let (Operands::Jump { .. }, Some(label)) = (instruction.operands(), label) {
+        return write!(f, "{}{separator}{label}", options.keyword(mnemonic));
+    }
+
+    match instruction.operands() {
+        Operands::Locations { src, dest } => {
+            let width = dest.width().or(src.width());
+
+            write!(
+                f,
+                "{}{separator}",
+                options.keyword(&suffixed(mnemonic, width))
+            )?;
+            write_location(f, src, options, symbol)?;
+            write!(f, ", ")?;
+            write_location(f, dest, options, symbol)
+        }
+        Operands::Immediate { data, dest } => {
+            let width = dest.width().unwrap_or(data.width());
+
+            write!(
+                f,
+                "{}{separator}${}, ",
+                options.keyword(&suffixed(mnemonic, Some(width))),
+                options.immediate(data),
+            )?;
+            write_location(f, dest, options, symbol)
+        }
+        // push and pop only move words, and a memory operand doesn't say so itself.
+        Operands::Stack { location } => {
+            write!(
+                f,
+                "{}{separator}",
+                options.keyword(&suffixed(mnemonic, Some(Width::Word)))
+            )?;
+            write_location(f, location, options, symbol)
+        }
+        Operands::Unary { width, dest } => {
+            write!(
+                f,
+                "{}{separator}",
+                options.keyword(&suffixed(mnemonic, Some(width)))
+            )?;
+            write_location(f, dest, options, symbol)
+        }
+        Operands::Jump { increment } => {
+            // GNU as has no raw-increment syntax, so express the target relative to
+            // the start of the jump instead: two bytes for short forms, three for near.
+            let relative = match increment {
+                Immediate::Byte(increment) => *increment as i16 + 2,
+                Immediate::Word(increment) => increment.wrapping_add(3),
+            };
+            let sign = if relative < 0 { "-" } else { "+" };
+
+            write!(
+                f,
+                "{}{separator}.{sign}{}",
+                options.keyword(mnemonic),
+                options.signed(relative),
+            )
+        }
+        Operands::None => write!(f, "{}", options.keyword(mnemonic)),
+    }
+}
+
+fn suffixed(mnemonic: &str, width: Option<Width>) -> String {
+    match width {
+        Some(Width::Byte) => format!("{mnemonic}b"),
+        Some(Width::Word) => format!("{mnemonic}w"),
+        None => mnemonic.to_string(),
+    }
+}
+
+fn write_location(
+    f: &mut fmt::Formatter<'_>,
+    location: &Location,
+    options: &FormatOptions,
+    symbol: Option<&str>,
+) -> fmt::Result {
+    let Location {
+        ref register,
+        is_mem_addr,
+        ref addr_calc,
+        ref displacement,
+    } = *location;
+
+    let register_name = |register: &dyn ToString| options.keyword(&register.to_string());
+
+    match register {
+        Some(register) if is_mem_addr => {
+            if let Some(symbol) = symbol {
+                write!(f, "{symbol}")?;
+            } else if let Some(displacement) =
+                displacement.filter(|displacement| *displacement != 0)
+            {
+                let sign = if displacement < 0 { "-" } else { "" };
+                write!(f, "{sign}{}", options.signed(displacement))?;
+            }
+            write!(f, "(%{}", register_name(register))?;
+            if let Some(addr_calc) = addr_calc {
+                write!(f, ",%{}", register_name(addr_calc))?;
+            }
+            write!(f, ")")
+        }
+        Some(register) => write!(f, "%{}", register_name(register)),
+        None => {
+            let direct_address = displacement.expect("displacement required for direct address");
+            write!(f, "{}", options.address(direct_address))
+        }
+    }
+}
@@ -0,0 +1,376 @@
+use std::{
+    collections::BTreeSet,
+    fmt::{self, Display},
+};
+
+use crate::{
+    cfg::Cfg,
+    instruction::{Instruction, Location, Register},
+    listing::Symbols,
+    sim::immediate_value,
+    Decoded,
+};
+
+/// What a near call leaves between BP and the first argument once the
+/// prologue has run: the saved BP and the return address.
+const FRAME_HEADER: i16 = 4;
+
+/// A procedure found from a call to it or from a `push bp; mov bp, sp` prologue.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Function {
+    pub entry: usize,
+    /// Blocks reachable from the entry without following calls or running into
+    /// another function's entry.
+    pub blocks: BTreeSet<usize>,
+    /// Whether it opens with the prologue, making `[bp - n]` its locals and
+    /// `[bp + n]` its arguments.
+    pub frame: bool,
+    /// Where each `mov sp, bp; pop bp; ret` epilogue starts. The `mov` is left
+    /// out when there are no locals to drop.
+    pub epilogues: Vec<usize>,
+    /// The `n` of every `[bp - n]` the body uses.
+    pub locals: BTreeSet<i16>,
+    /// Every `[bp + n]` the body uses, counted from the first argument.
+    pub args: BTreeSet<i16>,
+    /// Bytes every caller drops with `add sp, n` straight after the call, as in
+    /// the C convention where the caller cleans up.
+    pub caller_cleanup: Option<u16>,
+}
+
+impl Function {
+    pub fn name(&self) -> String {
+        format!("sub_{:04X}", self.entry)
+    }
+}
+
+impl Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())?;
+        if !self.frame {
+            return Ok(());
+        }
+
+        write!(f, ": bp frame")?;
+        if !self.locals.is_empty() {
+            let locals: Vec<String> = self.locals.iter().map(|n| local(*n)).collect();
+            write!(f, ", locals {}", locals.join(" "))?;
+        }
+        if !self.args.is_empty() {
+            let args: Vec<String> = self.args.iter().map(|n| arg(*n)).collect();
+            write!(f, ", args {}", args.join(" "))?;
+        }
+        if let Some(bytes) = self.caller_cleanup {
+            write!(f, ", callers pop {bytes} bytes")?;
+        }
+        Ok(())
+    }
+}
+
+fn local(n: i16) -> String {
+    format!("var_{n}")
+}
+
+fn arg(n: i16) -> String {
+    format!("arg_{n}")
+}
+
+/// Every function in `cfg`, by entry: the targets of calls, and anywhere a
+/// frame prologue starts even if no call to it was decoded.
+pub fn functions(cfg: &Cfg) -> Vec<Function> {
+    let calls: Vec<&Decoded> = cfg
+        .lines
+        .iter()
+        .filter(|decoded| matches!(decoded.instruction, Instruction::Call { .. }))
+        .collect();
+
+    let entries: BTreeSet<usize> = calls
+        .iter()
+        .filter_map(|call| call.jump_target())
+        .chain(
+            (0..cfg.lines.len())
+                .filter(|index| has_prologue(cfg, *index))
+                .map(|index| cfg.lines[index].offset),
+        )
+        .filter(|entry| cfg.block_containing(*entry).is_some())
+        .collect();
+    let entry_blocks: BTreeSet<usize> = entries
+        .iter()
+        .filter_map(|entry| cfg.block_at(*entry))
+        .collect();
+
+    entries
+        .into_iter()
+        .map(|entry| {
+            let start = cfg.line_at(entry).expect("entries are decoded offsets");
+            let blocks = body(cfg, entry, &entry_blocks);
+            let frame = has_prologue(cfg, start);
+
+            let mut epilogues = vec![];
+            let mut locals = BTreeSet::new();
+            let mut args = BTreeSet::new();
+            for index in lines(cfg, &blocks) {
+                epilogues.extend(epilogue_at(cfg, index));
+                match frame_slot(&cfg.lines[index]).filter(|_| frame) {
+                    Some(n) if n < 0 => locals.insert(-n),
+                    Some(n) => args.insert(n - FRAME_HEADER),
+                    None => false,
+                };
+            }
+
+            Function {
+                entry,
+                blocks,
+                frame,
+                epilogues,
+                locals,
+                args,
+                caller_cleanup: caller_cleanup(cfg, &calls, entry),
+            }
+        })
+        .collect()
+}
+
+/// Names for `functions` and their frame slots: a label on each entry, and
+/// `var_n` and `arg_n` in place of BP displacements in functions with a frame.
+pub fn symbols(cfg: &Cfg, functions: &[Function]) -> Symbols {
+    let mut symbols = Symbols::default();
+
+    for function in functions {
+        symbols.labels.insert(function.entry, function.name());
+        if !function.frame {
+            continue;
+        }
+
+        for index in lines(cfg, &function.blocks) {
+            let decoded = &cfg.lines[index];
+            let Some(slot) = frame_slot(decoded) else {
+                continue;
+            };
+            let name = match slot {
+                n if n < 0 => local(-n),
+                n => arg(n - FRAME_HEADER),
+            };
+            symbols.definitions.insert(name.clone(), slot);
+            symbols.operands.insert(decoded.offset, name);
+        }
+    }
+
+    symbols
+}
+
+/// Listing notes: what each function looks like at its entry, and where its
+/// epilogues start.
+pub fn annotations(functions: &[Function]) -> Vec<(usize, String)> {
+    functions
+        .iter()
+        .flat_map(|function| {
+            let epilogues = function
+                .epilogues
+                .iter()
+                .map(|offset| (*offset, format!("epilogue of {}", function.name())));
+            Some((function.entry, function.to_string()))
+                .into_iter()
+                .chain(epilogues)
+        })
+        .collect()
+}
+
+/// Indices into [`Cfg::lines`] of the instructions in `blocks`, in address order.
+fn lines<'a>(cfg: &'a Cfg, blocks: &'a BTreeSet<usize>) -> impl Iterator<Item = usize> + 'a {
+    blocks
+        .iter()
+        .flat_map(|block| cfg.blocks[*block].lines.clone())
+}
+
+/// Blocks reachable along edges from the one holding `entry`, stopping at the
+/// `entries` of other functions so that a tail jump or fallthrough into one
+/// doesn't claim its frame slots.
+fn body(cfg: &Cfg, entry: usize, entries: &BTreeSet<usize>) -> BTreeSet<usize> {
+    let start = cfg.block_containing(entry);
+    let mut blocks = BTreeSet::new();
+    let mut pending: Vec<usize> = start.into_iter().collect();
+
+    while let Some(block) = pending.pop() {
+        if Some(block) != start && entries.contains(&block) {
+            continue;
+        }
+        if blocks.insert(block) {
+            pending.extend(cfg.successors(block).map(|edge| edge.to));
+        }
+    }
+
+    blocks
+}
+
+/// Whether `push bp; mov bp, sp` starts at `cfg.lines[index]`.
+fn has_prologue(cfg: &Cfg, index: usize) -> bool {
+    let [push, mov] = [index, index + 1].map(|index| cfg.lines.get(index));
+    let (Some(push), Some(mov)) = (push, mov) else {
+        return false;
+    };
+
+    matches!(&push.instruction, Instruction::Push { src } if is_register(src, Register::BP))
+        && matches!(
+            &mov.instruction,
+            Instruction::Mov { src, dest }
+                if is_register(src, Register::SP) && is_register(dest, Register::BP)
+        )
+        && mov.offset == push.offset + push.size
+}
+
+/// Where the epilogue ending in the `ret` at `cfg.lines[index]` starts, if
+/// the `ret` follows `pop bp`, itself optionally after `mov sp, bp`.
+fn epilogue_at(cfg: &Cfg, index: usize) -> Option<usize> {
+    let is_pop_bp = |index: usize| {
+        let instruction = &cfg.lines[index].instruction;
+        matches!(instruction, Instruction::Pop { dest } if is_register(dest, Register::BP))
+    };
+    let is_mov_sp_bp = |index: usize| {
+        matches!(
+            &cfg.lines[index].instruction,
+            Instruction::Mov { src, dest }
+                if is_register(src, Register::BP) && is_register(dest, Register::SP)
+        )
+    };
+
+    if !matches!(cfg.lines[index].instruction, Instruction::Ret)
+        || index == 0
+        || !is_pop_bp(index - 1)
+    {
+        return None;
+    }
+
+    let start = match index >= 2 && is_mov_sp_bp(index - 2) {
+        true => index - 2,
+        false => index - 1,
+    };
+    Some(cfg.lines[start].offset)
+}
+
+/// The BP displacement of the instruction's memory operand, if it addresses
+/// the frame: below BP, or above the saved BP and return address. An index
+/// register makes it an element of something in the frame rather than a slot.
+fn frame_slot(decoded: &Decoded) -> Option<i16> {
+    let location = decoded.instruction.memory_operand()?;
+    let displacement = location.displacement.unwrap_or(0);
+
+    (location.register == Some(Register::BP)
+        && location.addr_calc.is_none()
+        && !(0..FRAME_HEADER).contains(&displacement))
+    .then_some(displacement)
+}
+
+fn is_register(location: &Location, register: Register) -> bool {
+    !location.is_mem_addr && location.register == Some(register)
+}
+
+/// The `n` of an `add sp, n` following every call to `entry`, if they agree.
+fn caller_cleanup(cfg: &Cfg, calls: &[&Decoded], entry: usize) -> Option<u16> {
+    let mut cleanups = calls
+        .iter()
+        .filter(|call| call.jump_target() == Some(entry))
+        .map(|call| {
+            let next = &cfg.lines[call.fallthrough().and_then(|next| cfg.line_at(next))?];
+            match &next.instruction {
+                Instruction::AddImmediate { data, dest } if is_register(dest, Register::SP) => {
+                    Some(immediate_value(data))
+                }
+                _ => None,
+            }
+        });
+
+    let first = cleanups.next()??;
+    cleanups
+        .all(|cleanup| cleanup == Some(first))
+        .then_some(first)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode_with_offsets;
+
+    fn found(bytes: &[u8]) -> (Cfg, Vec<Function>) {
+        let cfg = Cfg::new(decode_with_offsets(bytes));
+        let functions = functions(&cfg);
+        (cfg, functions)
+    }
+
+    #[test]
+    fn a_framed_function_names_its_slots() {
+        // call 7; add sp, 2; ret
+        // push bp; mov bp, sp; mov ax, [bp + 4]; mov [bp - 2], ax; mov sp, bp; pop bp; ret
+        let (_, functions) = found(&[
+            0xE8, 0x04, 0x00, 0x83, 0xC4, 0x02, 0xC3, 0x55, 0x89, 0xE5, 0x8B, 0x46, 0x04, 0x89,
+            0x46, 0xFE, 0x89, 0xEC, 0x5D, 0xC3,
+        ]);
+
+        assert_eq!(functions.len(), 1);
+        let function = &functions[0];
+        assert_eq!(function.epilogues, vec![16]);
+        assert_eq!(
+            function.to_string(),
+            "sub_0007: bp frame, locals var_2, args arg_0, callers pop 2 bytes"
+        );
+    }
+
+    #[test]
+    fn indexed_frame_accesses_are_not_slots() {
+        // call 7; add sp, 2; ret
+        // push bp; mov bp, sp; mov ax, [bp + si + 4]; mov [bp - 2], ax; mov sp, bp; pop bp; ret
+        let (_, functions) = found(&[
+            0xE8, 0x04, 0x00, 0x83, 0xC4, 0x02, 0xC3, 0x55, 0x89, 0xE5, 0x8B, 0x42, 0x04, 0x89,
+            0x46, 0xFE, 0x89, 0xEC, 0x5D, 0xC3,
+        ]);
+
+        assert_eq!(
+            functions[0].to_string(),
+            "sub_0007: bp frame, locals var_2, callers pop 2 bytes"
+        );
+    }
+
+    #[test]
+    fn a_function_without_a_prologue_has_no_frame() {
+        // call 4; ret; mov ax, [bp - 2]; ret
+        let (_, functions) = found(&[0xE8, 0x01, 0x00, 0xC3, 0x8B, 0x46, 0xFE, 0xC3]);
+
+        assert_eq!(functions.len(), 1);
+        let function = &functions[0];
+        assert!(!function.frame);
+        assert!(function.locals.is_empty());
+        assert_eq!(function.caller_cleanup, None);
+        assert_eq!(function.to_string(), "sub_0004");
+    }
+
+    #[test]
+    fn a_prologue_without_a_call_is_still_a_function() {
+        // ret; push bp; mov bp, sp; pop bp; ret
+        let (_, functions) = found(&[0xC3, 0x55, 0x89, 0xE5, 0x5D, 0xC3]);
+
+        assert_eq!(functions.len(), 1);
+        assert_eq!(functions[0].entry, 1);
+        assert_eq!(functions[0].epilogues, vec![4]);
+    }
+
+    #[test]
+    fn a_tail_jump_stops_at_the_next_function() {
+        // call 7; call 15; ret
+        // push bp; mov bp, sp; mov ax, [bp - 2]; jmp 15
+        // push bp; mov bp, sp; mov ax, [bp + 6]; pop bp; ret
+        let (cfg, functions) = found(&[
+            0xE8, 0x04, 0x00, 0xE8, 0x09, 0x00, 0xC3, 0x55, 0x89, 0xE5, 0x8B, 0x46, 0xFE, 0xEB,
+            0x00, 0x55, 0x89, 0xE5, 0x8B, 0x46, 0x06, 0x5D, 0xC3,
+        ]);
+
+        let [first, second] = &functions[..] else {
+            panic!("expected two functions, found {functions:?}");
+        };
+        assert_eq!(first.to_string(), "sub_0007: bp frame, locals var_2");
+        assert!(first.epilogues.is_empty());
+        assert_eq!(second.to_string(), "sub_000F: bp frame, args arg_2");
+
+        let symbols = symbols(&cfg, &functions);
+        assert_eq!(symbols.operands[&10], "var_2");
+        assert_eq!(symbols.operands[&18], "arg_2");
+    }
+}
@@ -0,0 +1,337 @@
+use std::{io, ops::Range};
+
+use crate::{
+    image::Image,
+    sim::{physical, MEMORY_SIZE},
+};
+
+/// Data from a hex file, merged into contiguous chunks of physical memory.
+#[derive(Debug, Default)]
+pub struct Records {
+    /// Chunks sorted by address, none overlapping or touching another.
+    pub chunks: Vec<(usize, Vec<u8>)>,
+    /// The physical address execution starts at, if the file names one.
+    pub start: Option<usize>,
+}
+
+impl Records {
+    fn insert(&mut self, address: usize, data: &[u8]) -> Result<(), io::Error> {
+        if address + data.len() > MEMORY_SIZE {
+            return Err(invalid(format!(
+                "record at {address:#07x} runs past the end of the 8086's 1MB"
+            )));
+        }
+
+        if !data.is_empty() {
+            self.chunks.push((address, data.to_vec()));
+        }
+        Ok(())
+    }
+
+    fn merge(mut self) -> Result<Self, io::Error> {
+        self.chunks.sort_by_key(|(address, _)| *address);
+
+        let mut merged: Vec<(usize, Vec<u8>)> = vec![];
+        for (address, data) in self.chunks {
+            match merged.last_mut() {
+                Some((last, last_data)) if *last + last_data.len() > address => {
+                    return Err(invalid(format!(
+                        "records at {:#07x} and {address:#07x} overlap",
+                        *last
+                    )));
+                }
+                Some((last, last_data)) if *last + last_data.len() == address => {
+                    last_data.extend_from_slice(&data);
+                }
+                _ => merged.push((address, data)),
+            }
+        }
+
+        self.chunks = merged;
+        Ok(self)
+    }
+
+    /// The stretches of memory between consecutive chunks that no record covers.
+    pub fn gaps(&self) -> Vec<Range<usize>> {
+        self.chunks
+            .windows(2)
+            .map(|pair| pair[0].0 + pair[0].1.len()..pair[1].0)
+            .collect()
+    }
+
+    /// Places every chunk at its physical address. CS is the 64K-aligned segment
+    /// holding the start address, or the lowest record if there is none, so the
+    /// decoded offsets are those the CPU would see; chunks below CS are loaded
+    /// but not decoded.
+    pub fn to_image(&self) -> Image {
+        let lowest = self.chunks.first().map_or(0, |(address, _)| *address);
+        let entry = self.start.unwrap_or(lowest);
+        let cs = ((entry >> 16) << 12) as u16;
+        let base = physical(cs, 0);
+
+        let end = self
+            .chunks
+            .last()
+            .map_or(0, |(address, data)| address + data.len());
+        let mut bytes = vec![0; end];
+        for (address, data) in &self.chunks {
+            bytes[*address..address + data.len()].copy_from_slice(data);
+        }
+
+        let code = self
+            .chunks
+            .iter()
+            .filter(|(address, data)| address + data.len() > base)
+            .map(|(address, data)| address.max(&base) - base..address + data.len() - base)
+            .collect();
+
+        Image {
+            bytes,
+            segment: 0,
+            code,
+            data: vec![],
+            cs,
+            ip: (entry - base) as u16,
+            ss: 0,
+            sp: 0,
+            ds: 0,
+            es: 0,
+        }
+    }
+}
+
+/// Parses Intel HEX: data records with 16-bit offsets, extended segment and
+/// extended linear address records to move the base, and either start record.
+pub fn parse_intel_hex(text: &str) -> Result<Records, io::Error> {
+    let mut records = Records::default();
+    let mut base = 0;
+
+    for (number, line) in numbered_lines(text) {
+        let at_line = |err: io::Error| invalid(format!("line {number}: {err}"));
+
+        let hex = line
+            .strip_prefix(':')
+            .ok_or_else(|| invalid(format!("line {number}: record does not start with ':'")))?;
+        let bytes = parse_hex_bytes(hex).map_err(at_line)?;
+
+        if bytes.len() < 5 || bytes.len() != bytes[0] as usize + 5 {
+            return Err(invalid(format!(
+                "line {number}: record length does not match"
+            )));
+        }
+        if bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) != 0 {
+            return Err(invalid(format!("line {number}: checksum mismatch")));
+        }
+
+        let offset = u16::from_be_bytes([bytes[1], bytes[2]]) as usize;
+        let data = &bytes[4..bytes.len() - 1];
+        let word = |data: &[u8]| u16::from_be_bytes([data[0], data[1]]) as usize;
+
+        match (bytes[3], data.len()) {
+            (0x00, _) => records.insert(base + offset, data).map_err(at_line)?,
+            (0x01, _) => break,
+            (0x02, 2) => base = word(data) << 4,
+            (0x03, 4) => records.start = Some((word(data) << 4) + word(&data[2..])),
+            (0x04, 2) => base = word(data) << 16,
+            (0x05, 4) => records.start = Some((word(data) << 16) + word(&data[2..])),
+            (kind, _) => {
+                return Err(invalid(format!(
+                    "line {number}: unsupported or malformed record type {kind:02X}"
+                )))
+            }
+        }
+    }
+
+    records.merge()
+}
+
+/// Parses Motorola S-records: S1/S2/S3 data with 16, 24 and 32-bit addresses and
+/// S7/S8/S9 start addresses. Headers and counts are checked and skipped.
+pub fn parse_srec(text: &str) -> Result<Records, io::Error> {
+    let mut records = Records::default();
+
+    for (number, line) in numbered_lines(text) {
+        let at_line = |err: io::Error| invalid(format!("line {number}: {err}"));
+
+        let mut chars = line.chars();
+        let kind = match (chars.next(), chars.next()) {
+            (Some('S'), Some(kind)) => kind,
+            _ => {
+                return Err(invalid(format!(
+                    "line {number}: record does not start with 'S'"
+                )))
+            }
+        };
+        let bytes = parse_hex_bytes(chars.as_str()).map_err(at_line)?;
+
+        if bytes.is_empty() || bytes.len() != bytes[0] as usize + 1 {
+            return Err(invalid(format!(
+                "line {number}: record length does not match"
+            )));
+        }
+        if bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) != 0xFF {
+            return Err(invalid(format!("line {number}: checksum mismatch")));
+        }
+
+        let address_size = match kind {
+            '0' | '1' | '5' | '9' => 2,
+            '2' | '6' | '8' => 3,
+            '3' | '7' => 4,
+            _ => {
+                return Err(invalid(format!(
+                    "line {number}: unknown record type S{kind}"
+                )))
+            }
+        };
+        if bytes.len() < address_size + 2 {
+            return Err(invalid(format!("line {number}: record is too short")));
+        }
+
+        let address = bytes[1..1 + address_size]
+            .iter()
+            .fold(0, |address, byte| (address << 8) | *byte as usize);
+        let data = &bytes[1 + address_size..bytes.len() - 1];
+
+        match kind {
+            '1' | '2' | '3' => records.insert(address, data).map_err(at_line)?,
+            '7' | '8' | '9' => records.start = Some(address),
+            _ => {}
+        }
+    }
+
+    records.merge()
+}
+
+fn numbered_lines(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty())
+}
+
+fn parse_hex_bytes(hex: &str) -> Result<Vec<u8>, io::Error> {
+    if !hex.is_ascii() {
+        return Err(invalid("record has non-ASCII characters".to_string()));
+    }
+    if !hex.len().is_multiple_of(2) {
+        return Err(invalid("odd number of hex digits".to_string()));
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| {
+            u8::from_str_radix(&hex[index..index + 2], 16)
+                .map_err(|_| invalid(format!("invalid hex digits {:?}", &hex[index..index + 2])))
+        })
+        .collect()
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(result: Result<Records, io::Error>) -> String {
+        result.unwrap_err().to_string()
+    }
+
+    #[test]
+    fn intel_hex_data_records_merge_into_chunks() {
+        let records =
+            parse_intel_hex(":03000000B8050040\n:01000300C339\n\n:01001000905F\n:00000001FF\n")
+                .unwrap();
+
+        assert_eq!(
+            records.chunks,
+            vec![(0, vec![0xB8, 0x05, 0x00, 0xC3]), (0x10, vec![0x90])]
+        );
+        assert_eq!(records.gaps(), vec![4..0x10]);
+        assert_eq!(records.start, None);
+    }
+
+    #[test]
+    fn intel_hex_extended_addresses_move_the_base() {
+        let segment =
+            parse_intel_hex(":020000021000EC\n:01000200906D\n:0400000310000000E9").unwrap();
+        assert_eq!(segment.chunks, vec![(0x10002, vec![0x90])]);
+        assert_eq!(segment.start, Some(0x10000));
+
+        let image = segment.to_image();
+        assert_eq!((image.cs, image.ip), (0x1000, 0));
+        assert_eq!(image.code, vec![2..3]);
+
+        let linear = parse_intel_hex(":020000040001F9\n:01000200906D").unwrap();
+        assert_eq!(linear.chunks, vec![(0x10002, vec![0x90])]);
+    }
+
+    #[test]
+    fn intel_hex_stops_at_the_end_of_file_record() {
+        let records = parse_intel_hex(":01000200906D\n:00000001FF\nnot a record").unwrap();
+
+        assert_eq!(records.chunks, vec![(2, vec![0x90])]);
+    }
+
+    #[test]
+    fn intel_hex_rejects_bad_checksums() {
+        assert_eq!(
+            error(parse_intel_hex(":01000300C339\n:03000000B8050041")),
+            "line 2: checksum mismatch"
+        );
+    }
+
+    #[test]
+    fn intel_hex_rejects_malformed_records() {
+        for text in [
+            "03000000B8050040",
+            ":04000000B8050040",
+            ":0",
+            ":zz",
+            ":a\u{e9}0",
+            ":",
+            ":00000006FA",
+        ] {
+            assert!(parse_intel_hex(text).is_err(), "{text:?} was accepted");
+        }
+    }
+
+    #[test]
+    fn intel_hex_rejects_overlapping_records() {
+        assert!(error(parse_intel_hex(":03000000B8050040\n:01000200906D")).contains("overlap"));
+    }
+
+    #[test]
+    fn srec_data_and_start_records() {
+        let records =
+            parse_srec("S0050000686929\nS1070100B80500C377\nS2050123459001\nS9030100FB").unwrap();
+
+        assert_eq!(
+            records.chunks,
+            vec![(0x100, vec![0xB8, 0x05, 0x00, 0xC3]), (0x12345, vec![0x90])]
+        );
+        assert_eq!(records.start, Some(0x100));
+    }
+
+    #[test]
+    fn srec_rejects_bad_checksums() {
+        assert_eq!(
+            error(parse_srec("S1070100B80500C378")),
+            "line 1: checksum mismatch"
+        );
+    }
+
+    #[test]
+    fn srec_rejects_malformed_records() {
+        for text in [
+            "1070100B80500C377",
+            "S1080100B80500C377",
+            "S4030100FB",
+            "S10200FD",
+            "S",
+        ] {
+            assert!(parse_srec(text).is_err(), "{text:?} was accepted");
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use std::ops::Range;
+
+use crate::{
+    decode_range,
+    instruction::Register,
+    sim::{physical, Cpu, Segment},
+    traverse::{traverse, Traversal},
+    Decoded,
+};
+
+/// A program laid out as it sits in memory, with the register state it starts
+/// from, so the decoder and simulator agree on every address.
+pub struct Image {
+    /// Memory contents from `segment:0` onwards.
+    pub bytes: Vec<u8>,
+    pub segment: u16,
+    /// Offsets from `cs:0` that hold code, in address order.
+    pub code: Vec<Range<usize>>,
+    /// Offsets from `cs:0` known to hold data, such as padding and signatures.
+    pub data: Vec<Range<usize>>,
+    pub cs: u16,
+    pub ip: u16,
+    pub ss: u16,
+    pub sp: u16,
+    pub ds: u16,
+    pub es: u16,
+}
+
+impl Image {
+    /// A bare binary whose first byte sits at `origin`, like nasm's `org`, with IP
+    /// pointing at it and every other register zeroed. An origin of 0 is the
+    /// decoder's original view of its input.
+    pub fn flat(bytes: Vec<u8>, origin: u16) -> Self {
+        let origin = origin as usize;
+        let code = origin..origin + bytes.len();
+
+        let mut memory = vec![0; origin];
+        memory.extend(bytes);
+
+        Self {
+            bytes: memory,
+            segment: 0,
+            code: vec![code],
+            data: vec![],
+            cs: 0,
+            ip: origin as u16,
+            ss: 0,
+            sp: 0,
+            ds: 0,
+            es: 0,
+        }
+    }
+
+    /// Memory from `cs:0` onwards, which decoded offsets and `code` are relative to.
+    pub fn code_bytes(&self) -> &[u8] {
+        let start = (self.cs.saturating_sub(self.segment) as usize) << 4;
+        &self.bytes[start.min(self.bytes.len())..]
+    }
+
+    pub fn decode(&self) -> Vec<Decoded> {
+        self.code
+            .iter()
+            .flat_map(|range| decode_range(self.code_bytes(), range.clone()))
+            .collect()
+    }
+
+    /// Decodes by following control flow from the entry point and any extra
+    /// `entries`, leaving code that nothing reaches as data.
+    pub fn traverse(&self, entries: &[usize]) -> Traversal {
+        let entries: Vec<usize> = Some(self.ip as usize)
+            .into_iter()
+            .chain(entries.iter().copied())
+            .collect();
+
+        traverse(self.code_bytes(), &self.code, &entries)
+    }
+
+    /// A CPU with the image in memory and its registers set up to start it.
+    pub fn load(&self) -> Cpu {
+        let mut cpu = Cpu::new();
+
+        cpu.load(physical(self.segment, 0), &self.bytes);
+        cpu.set_segment(Segment::CS, self.cs);
+        cpu.set_segment(Segment::SS, self.ss);
+        cpu.set_segment(Segment::DS, self.ds);
+        cpu.set_segment(Segment::ES, self.es);
+        cpu.set_register(Register::SP, self.sp);
+        cpu.ip = self.ip;
+
+        cpu
+    }
+
+    /// Whether the instruction at CS:IP lies in one of the image's code ranges.
+    pub fn is_code(&self, cpu: &Cpu) -> bool {
+        let base = physical(self.cs, 0);
+        let address = cpu.instruction_address();
+
+        self.code
+            .iter()
+            .any(|range| (base + range.start..base + range.end).contains(&address))
+    }
+}
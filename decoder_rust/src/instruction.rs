@@ -1,7 +1,6 @@
-use std::{
-    cmp::Ordering,
-    fmt::{self, Display},
-};
+use std::fmt::{self, Display};
+
+use crate::format::{self, FormatOptions, Formatted};
 
 pub enum Instruction {
     Mov { src: Location, dest: Location },
@@ -16,6 +15,18 @@ pub enum Instruction {
     Cmp { src: Location, dest: Location },
     CmpImmediate { data: Immediate, dest: Location },
 
+    Xor { src: Location, dest: Location },
+    XorImmediate { data: Immediate, dest: Location },
+
+    Test { src: Location, dest: Location },
+    TestImmediate { data: Immediate, dest: Location },
+
+    Inc { width: Width, dest: Location },
+    Dec { width: Width, dest: Location },
+
+    Push { src: Location },
+    Pop { dest: Location },
+
     Je { increment: Immediate },
     Jl { increment: Immediate },
     Jle { increment: Immediate },
@@ -37,123 +48,165 @@ pub enum Instruction {
     Loopnz { increment: Immediate },
     Jcxz { increment: Immediate },
 
+    Jmp { increment: Immediate },
+    Call { increment: Immediate },
+    Ret,
+
     Noop,
 }
 
-impl Display for Instruction {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl Instruction {
+    pub fn mnemonic(&self) -> &'static str {
         match self {
-            Instruction::Mov { src, dest } => {
-                write!(f, "mov {}, {}", dest, src)
-            }
-            Instruction::MovImmediate { data, dest } => {
-                write!(
-                    f,
-                    "mov {}, {}",
-                    dest,
-                    string_for_immediate(data, Some(dest))
-                )
-            }
-            Instruction::Add { src, dest } => {
-                write!(f, "add {}, {}", dest, src)
-            }
-            Instruction::AddImmediate { data, dest } => {
-                write!(
-                    f,
-                    "add {}, {}",
-                    dest,
-                    string_for_immediate(data, Some(dest))
-                )
-            }
-            Instruction::Sub { src, dest } => {
-                write!(f, "sub {}, {}", dest, src)
-            }
-            Instruction::SubImmediate { data, dest } => {
-                write!(
-                    f,
-                    "sub {}, {}",
-                    dest,
-                    string_for_immediate(data, Some(dest))
-                )
-            }
-            Instruction::Cmp { src, dest } => {
-                write!(f, "cmp {}, {}", dest, src)
-            }
-            Instruction::CmpImmediate { data, dest } => {
-                write!(
-                    f,
-                    "cmp {}, {}",
+            Instruction::Mov { .. } | Instruction::MovImmediate { .. } => "mov",
+            Instruction::Add { .. } | Instruction::AddImmediate { .. } => "add",
+            Instruction::Sub { .. } | Instruction::SubImmediate { .. } => "sub",
+            Instruction::Cmp { .. } | Instruction::CmpImmediate { .. } => "cmp",
+            Instruction::Xor { .. } | Instruction::XorImmediate { .. } => "xor",
+            Instruction::Test { .. } | Instruction::TestImmediate { .. } => "test",
+            Instruction::Inc { .. } => "inc",
+            Instruction::Dec { .. } => "dec",
+            Instruction::Push { .. } => "push",
+            Instruction::Pop { .. } => "pop",
+            Instruction::Je { .. } => "je",
+            Instruction::Jl { .. } => "jl",
+            Instruction::Jle { .. } => "jle",
+            Instruction::Jb { .. } => "jb",
+            Instruction::Jbe { .. } => "jbe",
+            Instruction::Jp { .. } => "jp",
+            Instruction::Jo { .. } => "jo",
+            Instruction::Js { .. } => "js",
+            Instruction::Jne { .. } => "jne",
+            Instruction::Jnl { .. } => "jnl",
+            Instruction::Jnle { .. } => "jnle",
+            Instruction::Jnb { .. } => "jnb",
+            Instruction::Jnbe { .. } => "jnbe",
+            Instruction::Jnp { .. } => "jnp",
+            Instruction::Jno { .. } => "jno",
+            Instruction::Jns { .. } => "jns",
+            Instruction::Loop { .. } => "loop",
+            Instruction::Loopz { .. } => "loopz",
+            Instruction::Loopnz { .. } => "loopnz",
+            Instruction::Jcxz { .. } => "jcxz",
+            Instruction::Jmp { .. } => "jmp",
+            Instruction::Call { .. } => "call",
+            Instruction::Ret => "ret",
+            Instruction::Noop => "noop",
+        }
+    }
+
+    pub fn operands(&self) -> Operands<'_> {
+        match self {
+            Instruction::Mov { src, dest }
+            | Instruction::Add { src, dest }
+            | Instruction::Sub { src, dest }
+            | Instruction::Cmp { src, dest }
+            | Instruction::Xor { src, dest }
+            | Instruction::Test { src, dest } => Operands::Locations { src, dest },
+            Instruction::MovImmediate { data, dest }
+            | Instruction::AddImmediate { data, dest }
+            | Instruction::SubImmediate { data, dest }
+            | Instruction::CmpImmediate { data, dest }
+            | Instruction::XorImmediate { data, dest }
+            | Instruction::TestImmediate { data, dest } => Operands::Immediate { data, dest },
+            Instruction::Inc { width, dest } | Instruction::Dec { width, dest } => {
+                Operands::Unary {
+                    width: *width,
                     dest,
-                    string_for_immediate(data, Some(dest))
-                )
-            }
-            Instruction::Je { increment } => {
-                write!(f, "je {}", string_for_immediate(increment, None))
-            }
-            Instruction::Jl { increment } => {
-                write!(f, "jl {}", string_for_immediate(increment, None))
-            }
-            Instruction::Jle { increment } => {
-                write!(f, "jle {}", string_for_immediate(increment, None))
-            }
-            Instruction::Jb { increment } => {
-                write!(f, "jb {}", string_for_immediate(increment, None))
-            }
-            Instruction::Jbe { increment } => {
-                write!(f, "jbe {}", string_for_immediate(increment, None))
-            }
-            Instruction::Jp { increment } => {
-                write!(f, "jp {}", string_for_immediate(increment, None))
-            }
-            Instruction::Jo { increment } => {
-                write!(f, "jo {}", string_for_immediate(increment, None))
-            }
-            Instruction::Js { increment } => {
-                write!(f, "js {}", string_for_immediate(increment, None))
-            }
-            Instruction::Jne { increment } => {
-                write!(f, "jne {}", string_for_immediate(increment, None))
-            }
-            Instruction::Jnl { increment } => {
-                write!(f, "jnl {}", string_for_immediate(increment, None))
-            }
-            Instruction::Jnle { increment } => {
-                write!(f, "jnle {}", string_for_immediate(increment, None))
-            }
-            Instruction::Jnb { increment } => {
-                write!(f, "jnb {}", string_for_immediate(increment, None))
-            }
-            Instruction::Jnbe { increment } => {
-                write!(f, "jnbe {}", string_for_immediate(increment, None))
-            }
-            Instruction::Jnp { increment } => {
-                write!(f, "jnp {}", string_for_immediate(increment, None))
+                }
             }
-            Instruction::Jno { increment } => {
-                write!(f, "jno {}", string_for_immediate(increment, None))
+            Instruction::Push { src: location } | Instruction::Pop { dest: location } => {
+                Operands::Stack { location }
             }
+            Instruction::Je { increment }
+            | Instruction::Jl { increment }
+            | Instruction::Jle { increment }
+            | Instruction::Jb { increment }
+            | Instruction::Jbe { increment }
+            | Instruction::Jp { increment }
+            | Instruction::Jo { increment }
+            | Instruction::Js { increment }
+            | Instruction::Jne { increment }
+            | Instruction::Jnl { increment }
+            | Instruction::Jnle { increment }
+            | Instruction::Jnb { increment }
+            | Instruction::Jnbe { increment }
+            | Instruction::Jnp { increment }
+            | Instruction::Jno { increment }
+            | Instruction::Jns { increment }
+            | Instruction::Loop { increment }
+            | Instruction::Loopz { increment }
+            | Instruction::Loopnz { increment }
+            | Instruction::Jcxz { increment }
+            | Instruction::Jmp { increment }
+            | Instruction::Call { increment } => Operands::Jump { increment },
+            Instruction::Ret | Instruction::Noop => Operands::None,
+        }
+    }
 
-            Instruction::Jns { increment } => {
-                write!(f, "jns {}", string_for_immediate(increment, None))
-            }
+    /// The operand that goes through memory; at most one does on the 8086.
+    pub fn memory_operand(&self) -> Option<&Location> {
+        match self.operands() {
+            Operands::Locations { src, dest } => [src, dest]
+                .into_iter()
+                .find(|location| location.is_mem_addr),
+            Operands::Immediate { dest, .. }
+            | Operands::Stack { location: dest }
+            | Operands::Unary { dest, .. } => Some(dest).filter(|dest| dest.is_mem_addr),
+            _ => None,
+        }
+    }
 
-            Instruction::Loop { increment } => {
-                write!(f, "loop {}", string_for_immediate(increment, None))
-            }
+    /// The CX-driven branches, `loop*` and `jcxz`, which only have a short form.
+    pub fn is_loop(&self) -> bool {
+        matches!(
+            self,
+            Instruction::Loop { .. }
+                | Instruction::Loopz { .. }
+                | Instruction::Loopnz { .. }
+                | Instruction::Jcxz { .. }
+        )
+    }
 
-            Instruction::Loopz { increment } => {
-                write!(f, "loopz {}", string_for_immediate(increment, None))
-            }
+    /// Whether execution can carry on to the next instruction, which it can't
+    /// after `jmp` or `ret`. Calls are assumed to return.
+    pub fn falls_through(&self) -> bool {
+        !matches!(self, Instruction::Jmp { .. } | Instruction::Ret)
+    }
 
-            Instruction::Loopnz { increment } => {
-                write!(f, "loopnz {}", string_for_immediate(increment, None))
-            }
+    pub fn formatted<'a>(&'a self, options: &'a FormatOptions) -> Formatted<'a> {
+        Formatted::new(self, options)
+    }
+}
 
-            Instruction::Jcxz { increment } => {
-                write!(f, "jcxz {}", string_for_immediate(increment, None))
-            }
-            Instruction::Noop => write!(f, "noop"),
-        }
+pub enum Operands<'a> {
+    Locations {
+        src: &'a Location,
+        dest: &'a Location,
+    },
+    Immediate {
+        data: &'a Immediate,
+        dest: &'a Location,
+    },
+    Jump {
+        increment: &'a Immediate,
+    },
+    /// What `push` reads or `pop` writes, next to the implicit SS:SP.
+    Stack {
+        location: &'a Location,
+    },
+    /// What `inc` or `dec` reads and writes back. A memory operand doesn't carry
+    /// its own width, so it comes along.
+    Unary {
+        width: Width,
+        dest: &'a Location,
+    },
+    None,
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.formatted(&FormatOptions::default()))
     }
 }
 
@@ -165,44 +218,22 @@ pub struct Location {
     pub displacement: Option<i16>,
 }
 
+impl Location {
+    pub fn width(&self) -> Option<Width> {
+        match self.register {
+            Some(ref register) if !self.is_mem_addr => Some(register.width()),
+            _ => None,
+        }
+    }
+}
+
 impl Display for Location {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let Location {
-            ref register,
-            is_mem_addr,
-            ref addr_calc,
-            ref displacement,
-        } = *self;
-
-        if let Some(register) = register {
-            if is_mem_addr {
-                let mut msg = register.to_string();
-                if let Some(addr_calc) = addr_calc {
-                    msg.push_str(&format!(" + {}", addr_calc));
-                }
-                if let Some(displacement) = displacement {
-                    match displacement.cmp(&0b0) {
-                        Ordering::Less => {
-                            msg.push_str(&format!(" - {}", -displacement));
-                        }
-                        Ordering::Greater => {
-                            msg.push_str(&format!(" + {}", displacement));
-                        }
-                        Ordering::Equal => {}
-                    };
-                }
-                write!(f, "[{}]", msg)
-            } else {
-                write!(f, "{}", register)
-            }
-        } else {
-            let direct_address = displacement.expect("displacement required for direct address");
-            write!(f, "[{}]", direct_address)
-        }
+        format::write_location(f, self, &FormatOptions::default(), None, None)
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Register {
     AL,
     CL,
@@ -222,6 +253,33 @@ pub enum Register {
     DI,
 }
 
+impl Register {
+    pub fn width(&self) -> Width {
+        match self {
+            Register::AL
+            | Register::CL
+            | Register::DL
+            | Register::BL
+            | Register::AH
+            | Register::CH
+            | Register::DH
+            | Register::BH => Width::Byte,
+            _ => Width::Word,
+        }
+    }
+
+    /// The word register this one is half of, or itself if it is one.
+    pub fn full(&self) -> Register {
+        match self {
+            Register::AL | Register::AH => Register::AX,
+            Register::CL | Register::CH => Register::CX,
+            Register::DL | Register::DH => Register::DX,
+            Register::BL | Register::BH => Register::BX,
+            _ => *self,
+        }
+    }
+}
+
 impl Display for Register {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", format!("{:?}", self).to_lowercase())
@@ -233,26 +291,17 @@ pub enum Immediate {
     Word(i16),
 }
 
-fn string_for_immediate(data: &Immediate, dest: Option<&Location>) -> String {
-    if let Some(Location { is_mem_addr, .. }) = dest {
-        if *is_mem_addr {
-            return match data {
-                Immediate::Byte(data) => {
-                    format!("byte {}", data)
-                }
-                Immediate::Word(data) => {
-                    format!("word {}", data)
-                }
-            };
+impl Immediate {
+    pub fn width(&self) -> Width {
+        match self {
+            Immediate::Byte(_) => Width::Byte,
+            Immediate::Word(_) => Width::Word,
         }
     }
+}
 
-    match data {
-        Immediate::Byte(data) => {
-            format!("{}", data)
-        }
-        Immediate::Word(data) => {
-            format!("{}", data)
-        }
-    }
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Width {
+    Byte,
+    Word,
 }
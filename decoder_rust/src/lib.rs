@@ -1,187 +1,352 @@
-use instruction::{Immediate, Instruction, Location};
+use std::ops::Range;
+
+use instruction::{Immediate, Instruction, Location, Operands, Width};
 
 use crate::{instruction::Register, utils::blice};
 
-mod instruction;
+pub mod batch;
+pub mod boot;
+pub mod cfg;
+pub mod constants;
+pub mod cycles;
+pub mod dos;
+pub mod dot;
+pub mod effects;
+pub mod equivalence;
+pub mod format;
+pub mod functions;
+pub mod hex;
+pub mod image;
+pub mod instruction;
+pub mod lint;
+pub mod listing;
+pub mod liveness;
+pub mod loops;
+pub mod nasm;
+pub mod peephole;
+pub mod sim;
+pub mod symbolic;
+pub mod traverse;
 pub mod utils;
 
+pub struct Decoded {
+    pub offset: usize,
+    pub size: usize,
+    pub instruction: Instruction,
+}
+
+impl Decoded {
+    /// Offset a jump or loop lands on when taken, counted from the end of the instruction.
+    pub fn jump_target(&self) -> Option<usize> {
+        match self.instruction.operands() {
+            Operands::Jump { increment } => {
+                let increment = match increment {
+                    Immediate::Byte(increment) => *increment as isize,
+                    Immediate::Word(increment) => *increment as isize,
+                };
+                (self.offset + self.size).checked_add_signed(increment)
+            }
+            _ => None,
+        }
+    }
+
+    /// Offset of the next instruction, if execution can continue there.
+    pub fn fallthrough(&self) -> Option<usize> {
+        self.instruction
+            .falls_through()
+            .then_some(self.offset + self.size)
+    }
+}
+
 pub fn decode(bytes: Vec<u8>) -> Vec<Instruction> {
-    let mut result: Vec<Instruction> = vec![];
-    let mut bytes = bytes.iter();
+    decode_with_offsets(&bytes)
+        .into_iter()
+        .map(|decoded| decoded.instruction)
+        .collect()
+}
 
-    let mut next_byte = || {
-        let byte = bytes.next();
+pub fn decode_with_offsets(bytes: &[u8]) -> Vec<Decoded> {
+    decode_range(bytes, 0..bytes.len())
+}
 
-        #[cfg(debug_assertions)]
-        if let Some(byte) = byte {
-            println!("{byte:08b}");
-        }
+/// Decodes `bytes[range]`, keeping offsets relative to the start of `bytes` so
+/// they and any jump targets stay absolute.
+pub fn decode_range(bytes: &[u8], range: Range<usize>) -> Vec<Decoded> {
+    let bytes = &bytes[..range.end];
+    let mut result: Vec<Decoded> = vec![];
+    let mut offset = range.start;
+
+    while offset < bytes.len() {
+        let decoded = decode_at(bytes, offset);
+        offset += decoded.size;
+        result.push(decoded);
+    }
 
-        byte
+    result
+}
+
+pub fn decode_at(bytes: &[u8], offset: usize) -> Decoded {
+    let mut size = 0;
+    let mut truncated = false;
+    let mut bytes = bytes[offset..].iter();
+
+    // Arbitrary binaries can end mid-instruction, so rather than threading that
+    // through every decode_* helper, pad with zeroes and discard the result below.
+    let mut next_byte = || match bytes.next() {
+        Some(byte) => {
+            size += 1;
+            Some(byte)
+        }
+        None => {
+            truncated = true;
+            Some(&0)
+        }
     };
 
-    while let Some(instruction_byte) = next_byte() {
-        result.push(match instruction_byte {
-            0b01110100 => Instruction::Je {
-                increment: decode_data(&mut next_byte, &0, &0),
-            },
-            0b01111100 => Instruction::Jl {
-                increment: decode_data(&mut next_byte, &0, &0),
-            },
-            0b01111110 => Instruction::Jle {
-                increment: decode_data(&mut next_byte, &0, &0),
-            },
-            0b01110010 => Instruction::Jb {
-                increment: decode_data(&mut next_byte, &0, &0),
-            },
-            0b01110110 => Instruction::Jbe {
-                increment: decode_data(&mut next_byte, &0, &0),
-            },
-            0b01111010 => Instruction::Jp {
-                increment: decode_data(&mut next_byte, &0, &0),
-            },
-            0b01110000 => Instruction::Jo {
-                increment: decode_data(&mut next_byte, &0, &0),
-            },
-            0b01111000 => Instruction::Js {
-                increment: decode_data(&mut next_byte, &0, &0),
-            },
-            0b01110101 => Instruction::Jne {
-                increment: decode_data(&mut next_byte, &0, &0),
-            },
-            0b01111101 => Instruction::Jnl {
-                increment: decode_data(&mut next_byte, &0, &0),
-            },
-            0b01111111 => Instruction::Jnle {
-                increment: decode_data(&mut next_byte, &0, &0),
-            },
-            0b01110011 => Instruction::Jnb {
-                increment: decode_data(&mut next_byte, &0, &0),
-            },
-            0b01110111 => Instruction::Jnbe {
-                increment: decode_data(&mut next_byte, &0, &0),
-            },
-            0b01111011 => Instruction::Jnp {
-                increment: decode_data(&mut next_byte, &0, &0),
-            },
-            0b01110001 => Instruction::Jno {
-                increment: decode_data(&mut next_byte, &0, &0),
-            },
-            0b01111001 => Instruction::Jns {
-                increment: decode_data(&mut next_byte, &0, &0),
-            },
-            0b11100010 => Instruction::Loop {
-                increment: decode_data(&mut next_byte, &0, &0),
-            },
-            0b11100001 => Instruction::Loopz {
-                increment: decode_data(&mut next_byte, &0, &0),
-            },
-            0b11100000 => Instruction::Loopnz {
-                increment: decode_data(&mut next_byte, &0, &0),
-            },
-            0b11100011 => Instruction::Jcxz {
-                increment: decode_data(&mut next_byte, &0, &0),
-            },
+    let instruction_byte = next_byte().expect("offset must be within bytes");
+
+    let instruction = match instruction_byte {
+        0b01110100 => Instruction::Je {
+            increment: decode_data(&mut next_byte, &0, &0),
+        },
+        0b01111100 => Instruction::Jl {
+            increment: decode_data(&mut next_byte, &0, &0),
+        },
+        0b01111110 => Instruction::Jle {
+            increment: decode_data(&mut next_byte, &0, &0),
+        },
+        0b01110010 => Instruction::Jb {
+            increment: decode_data(&mut next_byte, &0, &0),
+        },
+        0b01110110 => Instruction::Jbe {
+            increment: decode_data(&mut next_byte, &0, &0),
+        },
+        0b01111010 => Instruction::Jp {
+            increment: decode_data(&mut next_byte, &0, &0),
+        },
+        0b01110000 => Instruction::Jo {
+            increment: decode_data(&mut next_byte, &0, &0),
+        },
+        0b01111000 => Instruction::Js {
+            increment: decode_data(&mut next_byte, &0, &0),
+        },
+        0b01110101 => Instruction::Jne {
+            increment: decode_data(&mut next_byte, &0, &0),
+        },
+        0b01111101 => Instruction::Jnl {
+            increment: decode_data(&mut next_byte, &0, &0),
+        },
+        0b01111111 => Instruction::Jnle {
+            increment: decode_data(&mut next_byte, &0, &0),
+        },
+        0b01110011 => Instruction::Jnb {
+            increment: decode_data(&mut next_byte, &0, &0),
+        },
+        0b01110111 => Instruction::Jnbe {
+            increment: decode_data(&mut next_byte, &0, &0),
+        },
+        0b01111011 => Instruction::Jnp {
+            increment: decode_data(&mut next_byte, &0, &0),
+        },
+        0b01110001 => Instruction::Jno {
+            increment: decode_data(&mut next_byte, &0, &0),
+        },
+        0b01111001 => Instruction::Jns {
+            increment: decode_data(&mut next_byte, &0, &0),
+        },
+        0b11100010 => Instruction::Loop {
+            increment: decode_data(&mut next_byte, &0, &0),
+        },
+        0b11100001 => Instruction::Loopz {
+            increment: decode_data(&mut next_byte, &0, &0),
+        },
+        0b11100000 => Instruction::Loopnz {
+            increment: decode_data(&mut next_byte, &0, &0),
+        },
+        0b11100011 => Instruction::Jcxz {
+            increment: decode_data(&mut next_byte, &0, &0),
+        },
+        0b11101011 => Instruction::Jmp {
+            increment: decode_data(&mut next_byte, &0, &0),
+        },
+        0b11101001 => Instruction::Jmp {
+            increment: decode_data(&mut next_byte, &0, &1),
+        },
+        0b11101000 => Instruction::Call {
+            increment: decode_data(&mut next_byte, &0, &1),
+        },
+        0b11000011 => Instruction::Ret,
+
+        _ => match blice(instruction_byte, 0, 4) {
+            0b0000 | 0b0010 | 0b0011 => match blice(instruction_byte, 5, 1) {
+                0b0 => {
+                    let d = blice(instruction_byte, 6, 1);
+                    let w = blice(instruction_byte, 7, 1);
+
+                    let (src, dest) = decode_mod_reg_rm(&mut next_byte, &d, &w);
 
-            _ => match blice(instruction_byte, 0, 4) {
-                0b0000 | 0b0010 | 0b0011 => match blice(instruction_byte, 5, 1) {
+                    match blice(instruction_byte, 2, 3) {
+                        0b000 => Instruction::Add { src, dest },
+                        0b101 => Instruction::Sub { src, dest },
+                        0b110 => Instruction::Xor { src, dest },
+                        0b111 => Instruction::Cmp { src, dest },
+                        _ => Instruction::Noop,
+                    }
+                }
+                0b1 => match blice(instruction_byte, 6, 1) {
                     0b0 => {
-                        let d = blice(instruction_byte, 6, 1);
                         let w = blice(instruction_byte, 7, 1);
 
-                        let (src, dest) = decode_mod_reg_rm(&mut next_byte, &d, &w);
+                        let (data, dest) = decode_accum_immediate(&mut next_byte, &w);
 
                         match blice(instruction_byte, 2, 3) {
-                            0b000 => Instruction::Add { src, dest },
-                            0b101 => Instruction::Sub { src, dest },
-                            0b111 => Instruction::Cmp { src, dest },
+                            0b000 => Instruction::AddImmediate { data, dest },
+                            0b101 => Instruction::SubImmediate { data, dest },
+                            0b110 => Instruction::XorImmediate { data, dest },
+                            0b111 => Instruction::CmpImmediate { data, dest },
                             _ => Instruction::Noop,
                         }
                     }
-                    0b1 => match blice(instruction_byte, 6, 1) {
-                        0b0 => {
-                            let w = blice(instruction_byte, 7, 1);
-
-                            let (data, dest) = decode_accum_immediate(&mut next_byte, &w);
-
-                            match blice(instruction_byte, 2, 3) {
-                                0b000 => Instruction::AddImmediate { data, dest },
-                                0b101 => Instruction::SubImmediate { data, dest },
-                                0b111 => Instruction::CmpImmediate { data, dest },
-                                _ => Instruction::Noop,
-                            }
-                        }
-                        _ => Instruction::Noop,
-                    },
                     _ => Instruction::Noop,
                 },
-                0b1000 => match blice(instruction_byte, 4, 2) {
-                    0b00 => {
-                        let s = blice(instruction_byte, 6, 1);
-                        let w = blice(instruction_byte, 7, 1);
+                _ => Instruction::Noop,
+            },
+            0b1000 => match blice(instruction_byte, 4, 2) {
+                0b00 => {
+                    let s = blice(instruction_byte, 6, 1);
+                    let w = blice(instruction_byte, 7, 1);
 
-                        let (data, dest, ident) = decode_mod_rm(&mut next_byte, &s, &w);
+                    let (data, dest, ident) = decode_mod_rm(&mut next_byte, &s, &w);
 
-                        match ident {
-                            0b000 => Instruction::AddImmediate { data, dest },
-                            0b101 => Instruction::SubImmediate { data, dest },
-                            0b111 => Instruction::CmpImmediate { data, dest },
-                            _ => panic!(),
-                        }
+                    match ident {
+                        0b000 => Instruction::AddImmediate { data, dest },
+                        0b101 => Instruction::SubImmediate { data, dest },
+                        0b110 => Instruction::XorImmediate { data, dest },
+                        0b111 => Instruction::CmpImmediate { data, dest },
+                        _ => Instruction::Noop,
                     }
-                    0b10 => {
-                        let d = blice(instruction_byte, 6, 1);
-                        let w = blice(instruction_byte, 7, 1);
+                }
+                0b01 if blice(instruction_byte, 6, 1) == 0b0 => {
+                    let w = blice(instruction_byte, 7, 1);
 
-                        let (src, dest) = decode_mod_reg_rm(&mut next_byte, &d, &w);
+                    let (src, dest) = decode_mod_reg_rm(&mut next_byte, &0, &w);
 
-                        Instruction::Mov { src, dest }
-                    }
-                    _ => Instruction::Noop,
-                },
-                0b1010 => {
-                    let ident = blice(instruction_byte, 4, 3);
+                    Instruction::Test { src, dest }
+                }
+                0b10 => {
+                    let d = blice(instruction_byte, 6, 1);
                     let w = blice(instruction_byte, 7, 1);
 
-                    let (src, dest) = decode_accum_mem(&mut next_byte, &ident, &w);
+                    let (src, dest) = decode_mod_reg_rm(&mut next_byte, &d, &w);
 
                     Instruction::Mov { src, dest }
                 }
-                0b1011 => {
-                    let w = blice(instruction_byte, 4, 1);
-                    let reg_bits = blice(instruction_byte, 5, 3);
+                _ => Instruction::Noop,
+            },
+            0b1010 => match blice(instruction_byte, 4, 3) {
+                ident @ (0b000 | 0b001) => {
+                    let w = blice(instruction_byte, 7, 1);
 
-                    let reg = decode_register_reg(&reg_bits, &w);
-                    let dest = Location {
-                        register: reg,
-                        is_mem_addr: false,
-                        addr_calc: None,
-                        displacement: None,
-                    };
+                    let (src, dest) = decode_accum_mem(&mut next_byte, &ident, &w);
 
-                    let data = decode_data(&mut next_byte, &0, &w);
+                    Instruction::Mov { src, dest }
+                }
+                0b100 => {
+                    let w = blice(instruction_byte, 7, 1);
 
-                    Instruction::MovImmediate { data, dest }
+                    let (data, dest) = decode_accum_immediate(&mut next_byte, &w);
+
+                    Instruction::TestImmediate { data, dest }
                 }
-                0b1100 => match blice(instruction_byte, 4, 3) {
-                    0b011 => {
-                        let w = blice(instruction_byte, 7, 1);
+                _ => Instruction::Noop,
+            },
+            0b1011 => {
+                let w = blice(instruction_byte, 4, 1);
+                let reg_bits = blice(instruction_byte, 5, 3);
+
+                let reg = decode_register_reg(&reg_bits, &w);
+                let dest = Location {
+                    register: reg,
+                    is_mem_addr: false,
+                    addr_calc: None,
+                    displacement: None,
+                };
+
+                let data = decode_data(&mut next_byte, &0, &w);
+
+                Instruction::MovImmediate { data, dest }
+            }
+            0b0100 => {
+                let width = Width::Word;
+                let dest = Location {
+                    register: decode_register_reg(&blice(instruction_byte, 5, 3), &1),
+                    is_mem_addr: false,
+                    addr_calc: None,
+                    displacement: None,
+                };
+
+                match blice(instruction_byte, 4, 1) {
+                    0b0 => Instruction::Inc { width, dest },
+                    _ => Instruction::Dec { width, dest },
+                }
+            }
+            0b0101 => {
+                let location = Location {
+                    register: decode_register_reg(&blice(instruction_byte, 5, 3), &1),
+                    is_mem_addr: false,
+                    addr_calc: None,
+                    displacement: None,
+                };
+
+                match blice(instruction_byte, 4, 1) {
+                    0b0 => Instruction::Push { src: location },
+                    _ => Instruction::Pop { dest: location },
+                }
+            }
+            0b1100 => match blice(instruction_byte, 4, 3) {
+                0b011 => {
+                    let w = blice(instruction_byte, 7, 1);
 
-                        let (data, dest, _) = decode_mod_rm(&mut next_byte, &0, &w);
+                    let (data, dest, _) = decode_mod_rm(&mut next_byte, &0, &w);
 
-                        Instruction::MovImmediate { data, dest }
-                    }
-                    _ => Instruction::Noop,
-                },
+                    Instruction::MovImmediate { data, dest }
+                }
                 _ => Instruction::Noop,
             },
-        });
+            0b1111 => {
+                let w = blice(instruction_byte, 7, 1);
+                let width = if w == 0b1 { Width::Word } else { Width::Byte };
+
+                match blice(instruction_byte, 4, 3) {
+                    // Only `test` in this group takes an immediate, so read it once that's known.
+                    0b011 => match decode_rm(&mut next_byte, &w) {
+                        (dest, 0b000) => Instruction::TestImmediate {
+                            data: decode_data(&mut next_byte, &0, &w),
+                            dest,
+                        },
+                        _ => Instruction::Noop,
+                    },
+                    0b111 => match decode_rm(&mut next_byte, &w) {
+                        (dest, 0b000) => Instruction::Inc { width, dest },
+                        (dest, 0b001) => Instruction::Dec { width, dest },
+                        _ => Instruction::Noop,
+                    },
+                    _ => Instruction::Noop,
+                }
+            }
+            _ => Instruction::Noop,
+        },
+    };
 
-        #[cfg(debug_assertions)]
-        println!("{}", result.last().unwrap());
+    Decoded {
+        offset,
+        size,
+        instruction: if truncated {
+            Instruction::Noop
+        } else {
+            instruction
+        },
     }
-
-    result
 }
 
 fn decode_register_r_m(
@@ -271,6 +436,15 @@ fn decode_mod_rm<'a>(
     s: &u8,
     w: &u8,
 ) -> (Immediate, Location, u8) {
+    let (dest, ident) = decode_rm(next_byte, w);
+    let data = decode_data(next_byte, s, w);
+
+    (data, dest, ident)
+}
+
+/// Reads a mod-r/m byte whose reg field picks the operation instead of a
+/// register, returning the operand and that field.
+fn decode_rm<'a>(next_byte: &mut impl FnMut() -> Option<&'a u8>, w: &u8) -> (Location, u8) {
     let register_byte = next_byte().unwrap();
 
     let mod_bits = blice(register_byte, 0, 2);
@@ -279,16 +453,14 @@ fn decode_mod_rm<'a>(
 
     let (r_m, addr_calc) = decode_register_r_m(&r_m_bits, w, &mod_bits);
 
-    let dest = Location {
+    let location = Location {
         register: r_m,
         is_mem_addr: mod_bits != 0b11,
         addr_calc,
         displacement: decode_displacement(next_byte, &mod_bits, &r_m_bits),
     };
 
-    let data = decode_data(next_byte, s, w);
-
-    (data, dest, ident)
+    (location, ident)
 }
 
 fn decode_accum_mem<'a>(
@@ -298,7 +470,11 @@ fn decode_accum_mem<'a>(
 ) -> (Location, Location) {
     let accumulator = Location {
         is_mem_addr: false,
-        register: Some(Register::AX),
+        register: Some(if *w == 0b0 {
+            Register::AL
+        } else {
+            Register::AX
+        }),
         addr_calc: None,
         displacement: None,
     };
@@ -306,13 +482,13 @@ fn decode_accum_mem<'a>(
         is_mem_addr: true,
         register: None,
         addr_calc: None,
-        displacement: decode_addr(next_byte, w),
+        displacement: decode_addr(next_byte),
     };
 
     match *ident {
         0b000 => (mem, accumulator),
         0b001 => (accumulator, mem),
-        _ => panic!(),
+        _ => unreachable!(),
     }
 }
 
@@ -341,7 +517,7 @@ fn decode_data<'a>(next_byte: &mut impl FnMut() -> Option<&'a u8>, s: &u8, w: &u
         Immediate::Byte(i8::from_be_bytes([*lo]))
     } else if *s == 0b1 {
         let lo = next_byte().unwrap();
-        Immediate::Word(i16::from_be_bytes([0, *lo]))
+        Immediate::Word(i8::from_be_bytes([*lo]) as i16)
     } else {
         let lo = next_byte().unwrap();
         let hi = next_byte().unwrap();
@@ -349,15 +525,10 @@ fn decode_data<'a>(next_byte: &mut impl FnMut() -> Option<&'a u8>, s: &u8, w: &u
     }
 }
 
-fn decode_addr<'a>(next_byte: &mut impl FnMut() -> Option<&'a u8>, w: &u8) -> Option<i16> {
-    if *w == 0b0 {
-        let lo = next_byte().unwrap();
-        Some(u8::from_be_bytes([*lo]) as i16)
-    } else {
-        let lo = next_byte().unwrap();
-        let hi = next_byte().unwrap();
-        Some(u16::from_be_bytes([*hi, *lo]) as i16)
-    }
+fn decode_addr<'a>(next_byte: &mut impl FnMut() -> Option<&'a u8>) -> Option<i16> {
+    let lo = next_byte().unwrap();
+    let hi = next_byte().unwrap();
+    Some(u16::from_be_bytes([*hi, *lo]) as i16)
 }
 
 fn decode_displacement<'a>(
@@ -386,3 +557,114 @@ fn decode_displacement<'a>(
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listing(bytes: &[u8]) -> Vec<String> {
+        decode_with_offsets(bytes)
+            .iter()
+            .map(|decoded| decoded.instruction.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn sign_extended_immediates_keep_their_sign() {
+        // add ax, -1; cmp cx, -128; sub bx, 127
+        assert_eq!(
+            listing(&[0x83, 0xC0, 0xFF, 0x83, 0xF9, 0x80, 0x83, 0xEB, 0x7F]),
+            ["add ax, -1", "cmp cx, -128", "sub bx, 127"]
+        );
+    }
+
+    #[test]
+    fn accumulator_moves_take_a_full_word_address_for_either_width() {
+        assert_eq!(
+            listing(&[0xA0, 0x34, 0x12, 0xA3, 0x78, 0x56]),
+            ["mov al, [4660]", "mov [22136], ax"]
+        );
+    }
+
+    #[test]
+    fn byte_accumulator_moves_use_al() {
+        assert_eq!(
+            listing(&[0xA0, 0x34, 0x12, 0xA2, 0x78, 0x56, 0xA1, 0x34, 0x12]),
+            ["mov al, [4660]", "mov [22136], al", "mov ax, [4660]"]
+        );
+    }
+
+    #[test]
+    fn unknown_group_members_and_string_instructions_decode_as_noop() {
+        // or ax, 1; adc byte [bx], 7; movsb; lodsw
+        assert_eq!(
+            listing(&[0x83, 0xC8, 0x01, 0x80, 0x17, 0x07, 0xA4, 0xAD]),
+            ["noop", "noop", "noop", "noop"]
+        );
+    }
+
+    #[test]
+    fn a_truncated_final_instruction_is_a_noop_over_the_remaining_bytes() {
+        // mov ax, 0x1234 cut off after its first immediate byte
+        let lines = decode_with_offsets(&[0x90, 0xB8, 0x34]);
+        let last = lines.last().unwrap();
+        assert_eq!((last.offset, last.size), (1, 2));
+        assert_eq!(last.instruction.to_string(), "noop");
+    }
+
+    #[test]
+    fn xor_decodes_in_every_form() {
+        assert_eq!(
+            listing(&[
+                0x31, 0xC0, 0x32, 0x1F, 0x34, 0x05, 0x35, 0x34, 0x12, 0x83, 0xF3, 0x01, 0x80, 0x37,
+                0x07,
+            ]),
+            [
+                "xor ax, ax",
+                "xor bl, [bx]",
+                "xor al, 5",
+                "xor ax, 4660",
+                "xor bx, 1",
+                "xor [bx], byte 7",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_decodes_in_every_form() {
+        assert_eq!(
+            listing(&[
+                0x85, 0xD8, 0x84, 0x07, 0xA8, 0x01, 0xA9, 0x00, 0x01, 0xF6, 0xC3, 0x80, 0xF7, 0x47,
+                0x02, 0x34, 0x12,
+            ]),
+            [
+                "test ax, bx",
+                "test [bx], al",
+                "test al, 1",
+                "test ax, 256",
+                "test bl, -128",
+                "test [bx + 2], word 4660",
+            ]
+        );
+    }
+
+    #[test]
+    fn inc_and_dec_decode_in_every_form() {
+        assert_eq!(
+            listing(&[0x40, 0x4F, 0xFE, 0xC0, 0xFF, 0x0F, 0xFE, 0x4F, 0x03]),
+            [
+                "inc ax",
+                "dec di",
+                "inc al",
+                "dec word [bx]",
+                "dec byte [bx + 3]"
+            ]
+        );
+    }
+
+    #[test]
+    fn other_members_of_the_unary_groups_stay_undecoded() {
+        // not ax; push word [bx]
+        assert_eq!(listing(&[0xF7, 0xD0, 0xFF, 0x37]), ["noop", "noop"]);
+    }
+}
@@ -0,0 +1,285 @@
+use std::{
+    collections::BTreeSet,
+    fmt::{self, Display},
+};
+
+use crate::{
+    cfg::Cfg,
+    constants::{self, Constants},
+    cycles,
+    instruction::{Immediate, Instruction},
+    loops, Decoded,
+};
+
+/// Clocks to address memory through a single base register, the cheapest
+/// form that still uses one.
+const BASE_ONLY_CYCLES: u32 = 5;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Lint {
+    /// A word moved through an odd address pays for a second bus cycle. The
+    /// address is known when constant propagation could work it out; otherwise
+    /// only the displacement is, and the penalty depends on the registers.
+    OddAddress { address: Option<u16>, clocks: u32 },
+    /// `[bx + si + n]` and friends inside a loop, paying for the full address
+    /// calculation every iteration.
+    SlowAddress { clocks: u32 },
+    /// A near `jmp` whose target is close enough for the two-byte short form.
+    LongJump { target: usize },
+    /// Instructions nothing jumps, calls or falls into.
+    Unreachable { bytes: usize },
+}
+
+impl Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Lint::OddAddress {
+                address: Some(address),
+                clocks,
+            } => write!(
+                f,
+                "word access through odd address {address:#06x} costs {clocks} extra clocks"
+            ),
+            Lint::OddAddress {
+                address: None,
+                clocks,
+            } => write!(
+                f,
+                "word access through an odd displacement costs {clocks} extra clocks if the base is even"
+            ),
+            Lint::SlowAddress { clocks } => write!(
+                f,
+                "base + index + displacement takes {clocks} clocks every iteration, {} more than one register",
+                clocks - BASE_ONLY_CYCLES
+            ),
+            Lint::LongJump { target } => write!(
+                f,
+                "near jump to {target:04X} could be short, saving a byte for the same clocks"
+            ),
+            Lint::Unreachable { bytes } => {
+                write!(f, "{bytes} bytes from here are never reached; removing them saves no clocks")
+            }
+        }
+    }
+}
+
+/// A performance pitfall at the instruction at `offset`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    pub offset: usize,
+    pub lint: Lint,
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.lint)
+    }
+}
+
+/// Checks every instruction in `cfg` for the pitfalls in [`Lint`], in address
+/// order. `entries` are the offsets control arrives at from outside; anything
+/// they don't lead to, directly or through calls, counts as unreachable.
+pub fn lint(cfg: &Cfg, entries: &[usize]) -> Vec<Warning> {
+    let propagation = constants::propagate(cfg, &Constants::unknown());
+    let in_loops: BTreeSet<usize> = loops::natural_loops(cfg)
+        .iter()
+        .flat_map(|found| found.body.iter().copied())
+        .collect();
+    let reachable = reachable(cfg, entries);
+
+    let mut warnings = vec![];
+
+    for (index, block) in cfg.blocks.iter().enumerate() {
+        if !reachable.contains(&index) {
+            // One warning for a whole unreachable run, where it starts.
+            if index == 0 || reachable.contains(&(index - 1)) {
+                let bytes = (index..cfg.blocks.len())
+                    .take_while(|block| !reachable.contains(block))
+                    .map(|block| cfg.blocks[block].end - cfg.blocks[block].start)
+                    .sum();
+                warnings.push(Warning {
+                    offset: block.start,
+                    lint: Lint::Unreachable { bytes },
+                });
+            }
+            continue;
+        }
+
+        for line in block.lines.clone() {
+            let decoded = &cfg.lines[line];
+            warnings.extend(
+                odd_address(decoded, &propagation.before[line])
+                    .into_iter()
+                    .chain(slow_address(decoded).filter(|_| in_loops.contains(&index)))
+                    .chain(long_jump(decoded))
+                    .map(|lint| Warning {
+                        offset: decoded.offset,
+                        lint,
+                    }),
+            );
+        }
+    }
+
+    warnings
+}
+
+fn odd_address(decoded: &Decoded, constants: &Constants) -> Option<Lint> {
+    let transfers = cycles::word_transfers(&decoded.instruction);
+    let location = decoded.instruction.memory_operand()?;
+    if transfers == 0 {
+        return None;
+    }
+
+    let clocks = 4 * transfers;
+    match constants.effective_address(location) {
+        Some(address) => (address % 2 == 1).then_some(Lint::OddAddress {
+            address: Some(address),
+            clocks,
+        }),
+        None => (location.displacement.unwrap_or(0) % 2 != 0).then_some(Lint::OddAddress {
+            address: None,
+            clocks,
+        }),
+    }
+}
+
+fn slow_address(decoded: &Decoded) -> Option<Lint> {
+    let location = decoded.instruction.memory_operand()?;
+    let has_displacement = location
+        .displacement
+        .is_some_and(|displacement| displacement != 0);
+
+    (location.register.is_some() && location.addr_calc.is_some() && has_displacement).then(|| {
+        Lint::SlowAddress {
+            clocks: cycles::effective_address_cycles(location),
+        }
+    })
+}
+
+fn long_jump(decoded: &Decoded) -> Option<Lint> {
+    let Instruction::Jmp {
+        increment: Immediate::Word(increment),
+    } = decoded.instruction
+    else {
+        return None;
+    };
+
+    // The short form is a byte shorter, so the same target is one byte further.
+    i8::try_from(increment as i32 + 1).ok()?;
+    decoded
+        .jump_target()
+        .map(|target| Lint::LongJump { target })
+}
+
+/// Blocks reachable from the blocks holding `entries`, along edges and into
+/// the targets of calls in blocks already reached, so a helper that only dead
+/// code calls is dead too.
+fn reachable(cfg: &Cfg, entries: &[usize]) -> BTreeSet<usize> {
+    let mut pending: Vec<usize> = entries
+        .iter()
+        .filter_map(|offset| cfg.block_containing(*offset))
+        .collect();
+
+    let mut reachable = BTreeSet::new();
+    while let Some(block) = pending.pop() {
+        if !reachable.insert(block) {
+            continue;
+        }
+
+        pending.extend(cfg.successors(block).map(|edge| edge.to));
+        pending.extend(
+            cfg.instructions(block)
+                .iter()
+                .filter(|decoded| matches!(decoded.instruction, Instruction::Call { .. }))
+                .filter_map(Decoded::jump_target)
+                .filter_map(|target| cfg.block_containing(target)),
+        );
+    }
+
+    reachable
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode_with_offsets;
+
+    fn lints(bytes: &[u8]) -> Vec<(usize, Lint)> {
+        lint(&Cfg::new(decode_with_offsets(bytes)), &[0])
+            .into_iter()
+            .map(|warning| (warning.offset, warning.lint))
+            .collect()
+    }
+
+    #[test]
+    fn odd_word_addresses_are_flagged() {
+        // mov bx, 1; mov ax, [bx]; ret
+        assert_eq!(
+            lints(&[0xBB, 0x01, 0x00, 0x8B, 0x07, 0xC3]),
+            vec![(
+                3,
+                Lint::OddAddress {
+                    address: Some(1),
+                    clocks: 4
+                }
+            )]
+        );
+        // mov ax, [si + 1]; ret
+        assert_eq!(
+            lints(&[0x8B, 0x44, 0x01, 0xC3]),
+            vec![(
+                0,
+                Lint::OddAddress {
+                    address: None,
+                    clocks: 4
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn even_addresses_and_bytes_are_not() {
+        // mov bx, 2; mov ax, [bx]; mov al, [si + 1]; ret
+        assert_eq!(
+            lints(&[0xBB, 0x02, 0x00, 0x8B, 0x07, 0x8A, 0x44, 0x01, 0xC3]),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn full_address_calculations_are_flagged_only_in_loops() {
+        // mov ax, [bx + si + 4]; loop 0; ret
+        assert_eq!(
+            lints(&[0x8B, 0x40, 0x04, 0xE2, 0xFB, 0xC3]),
+            vec![(0, Lint::SlowAddress { clocks: 11 })]
+        );
+        // mov ax, [bx + si + 4]; ret
+        assert_eq!(lints(&[0x8B, 0x40, 0x04, 0xC3]), vec![]);
+    }
+
+    #[test]
+    fn near_jumps_that_would_fit_in_a_byte_are_flagged() {
+        // jmp near 3; ret
+        assert_eq!(
+            lints(&[0xE9, 0x00, 0x00, 0xC3]),
+            vec![(0, Lint::LongJump { target: 3 })]
+        );
+
+        // jmp near 203; ret
+        let far = lints(&[0xE9, 0xC8, 0x00, 0xC3]);
+        assert!(!far
+            .iter()
+            .any(|(_, lint)| matches!(lint, Lint::LongJump { .. })));
+    }
+
+    #[test]
+    fn helpers_called_only_from_dead_code_are_unreachable() {
+        // ret; call 5; ret; ret
+        assert_eq!(
+            lints(&[0xC3, 0xE8, 0x01, 0x00, 0xC3, 0xC3]),
+            vec![(1, Lint::Unreachable { bytes: 5 })]
+        );
+        // call 4; ret; ret
+        assert_eq!(lints(&[0xE8, 0x01, 0x00, 0xC3, 0xC3]), vec![]);
+    }
+}
@@ -0,0 +1,446 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt::{self, Display},
+    ops::Range,
+};
+
+use itertools::Itertools;
+
+use crate::{
+    format::{FormatOptions, Formatted, FormattedData, Syntax},
+    Decoded,
+};
+
+/// Names to print in place of numbers: labels for offsets, and symbols for the
+/// displacement of an instruction's memory operand, keyed by its offset.
+#[derive(Debug, Default, Clone)]
+pub struct Symbols {
+    pub labels: BTreeMap<usize, String>,
+    pub operands: BTreeMap<usize, String>,
+    /// The value behind each operand symbol, for output that has to define them.
+    pub definitions: BTreeMap<String, i16>,
+}
+
+impl Symbols {
+    /// `decoded` formatted with its operand symbol, if it has one.
+    pub fn formatted<'a>(
+        &'a self,
+        decoded: &'a Decoded,
+        options: &'a FormatOptions,
+    ) -> Formatted<'a> {
+        decoded
+            .instruction
+            .formatted(options)
+            .with_symbol(self.operands.get(&decoded.offset).map(String::as_str))
+    }
+}
+
+/// Bytes at `offset` that hold data rather than code, printed as `db` directives.
+#[derive(Debug, Clone, Copy)]
+pub struct Data<'a> {
+    pub offset: usize,
+    pub bytes: &'a [u8],
+}
+
+impl<'a> Data<'a> {
+    pub fn from_ranges(bytes: &'a [u8], ranges: &[Range<usize>]) -> Vec<Self> {
+        ranges
+            .iter()
+            .map(|range| Data {
+                offset: range.start,
+                bytes: &bytes[range.clone()],
+            })
+            .collect()
+    }
+
+    /// Splits into printable rows: each run of zero padding on its own, and the
+    /// rest eight bytes at a time.
+    fn rows(&self) -> Vec<Data<'a>> {
+        let end = self.bytes.len();
+        let padding_at = |index: usize| {
+            self.bytes[index..].len() >= DATA_ROW
+                && self.bytes[index..index + DATA_ROW]
+                    .iter()
+                    .all(|byte| *byte == 0)
+        };
+
+        let mut rows = vec![];
+        let mut start = 0;
+
+        while start < end {
+            let next = if padding_at(start) {
+                start
+                    + self.bytes[start..]
+                        .iter()
+                        .take_while(|byte| **byte == 0)
+                        .count()
+            } else {
+                (start + 1..end.min(start + DATA_ROW))
+                    .find(|&index| padding_at(index))
+                    .unwrap_or(end.min(start + DATA_ROW))
+            };
+
+            rows.push(Data {
+                offset: self.offset + start,
+                bytes: &self.bytes[start..next],
+            });
+            start = next;
+        }
+
+        rows
+    }
+}
+
+const DATA_ROW: usize = 8;
+
+enum Row<'a, 'b> {
+    Code(&'b Decoded),
+    Data(Data<'a>),
+}
+
+impl Row<'_, '_> {
+    fn offset(&self) -> usize {
+        match self {
+            Row::Code(decoded) => decoded.offset,
+            Row::Data(data) => data.offset,
+        }
+    }
+}
+
+fn rows<'a, 'b>(lines: &'b [Decoded], data: &[Data<'a>]) -> Vec<Row<'a, 'b>> {
+    let mut rows: Vec<Row> = lines
+        .iter()
+        .map(Row::Code)
+        .chain(data.iter().flat_map(Data::rows).map(Row::Data))
+        .collect();
+    rows.sort_by_key(Row::offset);
+    rows
+}
+
+pub struct Listing<'a> {
+    pub bytes: &'a [u8],
+    pub lines: Vec<Decoded>,
+    pub data: Vec<Data<'a>>,
+    /// Notes from analyses, printed after the instruction at their offset.
+    pub comments: BTreeMap<usize, Vec<String>>,
+    pub symbols: Symbols,
+    pub options: FormatOptions,
+}
+
+impl<'a> Listing<'a> {
+    pub fn new(bytes: &'a [u8], lines: Vec<Decoded>) -> Self {
+        Self {
+            bytes,
+            lines,
+            data: vec![],
+            comments: BTreeMap::new(),
+            symbols: Symbols::default(),
+            options: FormatOptions::default(),
+        }
+    }
+
+    pub fn with_options(mut self, options: FormatOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn with_data(mut self, data: Vec<Data<'a>>) -> Self {
+        self.data = data;
+        self
+    }
+
+    /// Adds `comments` to any already attached to the same offsets.
+    pub fn with_comments(mut self, comments: impl IntoIterator<Item = (usize, String)>) -> Self {
+        for (offset, comment) in comments {
+            self.comments.entry(offset).or_default().push(comment);
+        }
+        self
+    }
+
+    /// Prints labels on lines of their own and operand symbols in place of
+    /// displacements.
+    pub fn with_symbols(mut self, symbols: Symbols) -> Self {
+        self.symbols = symbols;
+        self
+    }
+
+    fn hex_for(&self, decoded: &Decoded) -> String {
+        hex(&self.bytes[decoded.offset..decoded.offset + decoded.size])
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02X}")).join(" ")
+}
+
+impl Display for Listing<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hex_width = self
+            .lines
+            .iter()
+            .map(|decoded| decoded.size * 3 - 1)
+            .max()
+            .unwrap_or(0);
+
+        // Only pad instructions out to a common width when there are comments to line up.
+        let instruction_width = match self.comments.is_empty() {
+            true => 0,
+            false => self
+                .lines
+                .iter()
+                .map(|decoded| {
+                    let instruction = self.symbols.formatted(decoded, &self.options);
+                    instruction.to_string().len()
+                })
+                .max()
+                .unwrap_or(0),
+        };
+
+        for row in rows(&self.lines, &self.data) {
+            match row {
+                Row::Code(decoded) => {
+                    if let Some(label) = self.symbols.labels.get(&decoded.offset) {
+                        writeln!(f, "{label}:")?;
+                    }
+
+                    let instruction = self.symbols.formatted(decoded, &self.options).to_string();
+                    write!(
+                        f,
+                        "{:04X}: {:<hex_width$}    ",
+                        decoded.offset,
+                        self.hex_for(decoded),
+                    )?;
+
+                    match self.comments.get(&decoded.offset) {
+                        Some(comments) => writeln!(
+                            f,
+                            "{instruction:<instruction_width$}    ; {}",
+                            comments.join("; ")
+                        )?,
+                        None => writeln!(f, "{instruction}")?,
+                    }
+                }
+                Row::Data(data) => {
+                    // Padding runs can be hundreds of bytes, so only show what fits.
+                    let shown = (hex_width + 1) / 3;
+                    let mut hex = hex(&data.bytes[..data.bytes.len().min(shown.max(1))]);
+                    if data.bytes.len() > shown.max(1) {
+                        hex.replace_range(hex.len() - 2.., "..");
+                    }
+
+                    writeln!(
+                        f,
+                        "{:04X}: {hex:<hex_width$}    {}",
+                        data.offset,
+                        FormattedData::new(data.bytes, &self.options),
+                    )?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Assembler-ready output: no addresses, but jump targets get label lines so the
+/// result can be reassembled or pasted back into a source file.
+pub struct Program<'a> {
+    pub lines: Vec<Decoded>,
+    pub data: Vec<Data<'a>>,
+    pub symbols: Symbols,
+    pub options: FormatOptions,
+}
+
+impl<'a> Program<'a> {
+    pub fn new(lines: Vec<Decoded>, options: FormatOptions) -> Self {
+        Self {
+            lines,
+            data: vec![],
+            symbols: Symbols::default(),
+            options,
+        }
+    }
+
+    pub fn with_data(mut self, data: Vec<Data<'a>>) -> Self {
+        self.data = data;
+        self
+    }
+
+    /// Uses the labels in `symbols` instead of generated ones, and defines its
+    /// operand symbols before the first instruction.
+    pub fn with_symbols(mut self, symbols: Symbols) -> Self {
+        self.symbols = symbols;
+        self
+    }
+
+    fn labels(&self) -> BTreeMap<usize, String> {
+        let starts: HashSet<usize> = self.lines.iter().map(|decoded| decoded.offset).collect();
+
+        let mut labels: BTreeMap<usize, String> = self
+            .lines
+            .iter()
+            .filter_map(Decoded::jump_target)
+            .filter(|target| starts.contains(target))
+            .map(|target| (target, format!("L{target:04X}")))
+            .collect();
+        labels.extend(
+            self.symbols
+                .labels
+                .iter()
+                .filter(|(offset, _)| starts.contains(offset))
+                .map(|(offset, label)| (*offset, label.clone())),
+        );
+        labels
+    }
+}
+
+impl Display for Program<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let labels = self.labels();
+
+        for (symbol, value) in &self.symbols.definitions {
+            match self.options.syntax {
+                Syntax::Nasm | Syntax::Masm => {
+                    writeln!(f, "{symbol} {} {value}", self.options.keyword("equ"))?
+                }
+                Syntax::Att => writeln!(f, "{} {symbol}, {value}", self.options.keyword(".set"))?,
+            }
+        }
+
+        for row in rows(&self.lines, &self.data) {
+            let decoded = match row {
+                Row::Code(decoded) => decoded,
+                Row::Data(data) => {
+                    writeln!(f, "{}", FormattedData::new(data.bytes, &self.options))?;
+                    continue;
+                }
+            };
+
+            if let Some(label) = labels.get(&decoded.offset) {
+                writeln!(f, "{label}:")?;
+            }
+
+            let label = decoded
+                .jump_target()
+                .and_then(|target| labels.get(&target))
+                .map(String::as_str);
+
+            writeln!(
+                f,
+                "{}",
+                self.symbols
+                    .formatted(decoded, &self.options)
+                    .with_label(label)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode_with_offsets;
+
+    /// mov cx, 3; add ax, cx; sub cx, 1; jne 3; ret
+    const COUNTDOWN: [u8; 11] = [
+        0xB9, 0x03, 0x00, 0x01, 0xC8, 0x83, 0xE9, 0x01, 0x75, 0xF9, 0xC3,
+    ];
+
+    #[test]
+    fn listing_pads_the_hex_column_to_the_longest_instruction() {
+        let listing = Listing::new(&COUNTDOWN, decode_with_offsets(&COUNTDOWN));
+
+        assert_eq!(
+            listing.to_string(),
+            "\
+0000: B9 03 00    mov cx, 3
+0003: 01 C8       add ax, cx
+0005: 83 E9 01    sub cx, 1
+0008: 75 F9       jne -7
+000A: C3          ret
+"
+        );
+    }
+
+    #[test]
+    fn listing_lines_up_comments_and_puts_labels_above_their_line() {
+        let mut symbols = Symbols::default();
+        symbols.labels.insert(3, "again".to_string());
+
+        let listing = Listing::new(&COUNTDOWN, decode_with_offsets(&COUNTDOWN))
+            .with_symbols(symbols)
+            .with_comments([(3, "3 clocks".to_string()), (8, "taken".to_string())]);
+
+        assert_eq!(
+            listing.to_string(),
+            "\
+0000: B9 03 00    mov cx, 3
+again:
+0003: 01 C8       add ax, cx    ; 3 clocks
+0005: 83 E9 01    sub cx, 1
+0008: 75 F9       jne -7        ; taken
+000A: C3          ret
+"
+        );
+    }
+
+    #[test]
+    fn listing_interleaves_data_rows_by_offset() {
+        // ret, then three bytes of data
+        let bytes = [0xC3, 0x01, 0x02, 0x03];
+        let lines = decode_with_offsets(&bytes[..1]);
+        let data = 1..bytes.len();
+        let listing = Listing::new(&bytes, lines).with_data(Data::from_ranges(&bytes, &[data]));
+
+        assert_eq!(
+            listing.to_string(),
+            "0000: C3    ret\n0001: ..    db 1, 2, 3\n"
+        );
+    }
+
+    #[test]
+    fn program_labels_jump_targets() {
+        let program = Program::new(decode_with_offsets(&COUNTDOWN), FormatOptions::default());
+
+        assert_eq!(
+            program.to_string(),
+            "\
+mov cx, 3
+L0003:
+add ax, cx
+sub cx, 1
+jne L0003
+ret
+"
+        );
+    }
+
+    #[test]
+    fn program_prefers_given_labels_and_defines_operand_symbols() {
+        // mov ax, [bp + 4]; ret
+        let bytes = [0x8B, 0x46, 0x04, 0xC3];
+        let mut symbols = Symbols::default();
+        symbols.labels.insert(0, "start".to_string());
+        symbols.operands.insert(0, "arg_4".to_string());
+        symbols.definitions.insert("arg_4".to_string(), 4);
+
+        let program = |syntax| {
+            let options = FormatOptions {
+                syntax,
+                ..Default::default()
+            };
+            Program::new(decode_with_offsets(&bytes), options)
+                .with_symbols(symbols.clone())
+                .to_string()
+        };
+
+        assert_eq!(
+            program(Syntax::Nasm),
+            "arg_4 equ 4\nstart:\nmov ax, [bp + arg_4]\nret\n"
+        );
+        assert_eq!(
+            program(Syntax::Att),
+            ".set arg_4, 4\nstart:\nmovw arg_4(%bp), %ax\nret\n"
+        );
+    }
+}
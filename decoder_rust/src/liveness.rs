@@ -0,0 +1,292 @@
+use std::{
+    collections::BTreeSet,
+    fmt::{self, Display},
+};
+
+use crate::{
+    cfg::Cfg,
+    effects::{self, Flag, ARITHMETIC_FLAGS},
+    instruction::{Instruction, Register},
+};
+
+/// Smallest pieces of the register file a write can replace on its own: each
+/// half of AX-DX, and the other word registers whole.
+const PARTS: [Register; 12] = [
+    Register::AL,
+    Register::AH,
+    Register::CL,
+    Register::CH,
+    Register::DL,
+    Register::DH,
+    Register::BL,
+    Register::BH,
+    Register::SP,
+    Register::BP,
+    Register::SI,
+    Register::DI,
+];
+
+fn parts(register: Register) -> &'static [Register] {
+    match register {
+        Register::AX => &[Register::AL, Register::AH],
+        Register::CX => &[Register::CL, Register::CH],
+        Register::DX => &[Register::DL, Register::DH],
+        Register::BX => &[Register::BL, Register::BH],
+        Register::AL => &[Register::AL],
+        Register::AH => &[Register::AH],
+        Register::CL => &[Register::CL],
+        Register::CH => &[Register::CH],
+        Register::DL => &[Register::DL],
+        Register::DH => &[Register::DH],
+        Register::BL => &[Register::BL],
+        Register::BH => &[Register::BH],
+        Register::SP => &[Register::SP],
+        Register::BP => &[Register::BP],
+        Register::SI => &[Register::SI],
+        Register::DI => &[Register::DI],
+    }
+}
+
+/// Registers and flags whose current values may still be read.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Live {
+    /// Stored by part, so `al` can be live while `ah` isn't.
+    parts: BTreeSet<Register>,
+    pub flags: BTreeSet<Flag>,
+}
+
+impl Live {
+    /// Everything, as assumed wherever control leaves what the graph can see.
+    pub fn all() -> Self {
+        Self {
+            parts: PARTS.into_iter().collect(),
+            flags: ARITHMETIC_FLAGS.into_iter().collect(),
+        }
+    }
+
+    /// Whether any part of `register` is live.
+    pub fn contains(&self, register: Register) -> bool {
+        parts(register).iter().any(|part| self.parts.contains(part))
+    }
+
+    /// The live registers, naming a word register when both of its halves are live.
+    pub fn registers(&self) -> Vec<Register> {
+        let mut registers = vec![];
+        for register in PARTS {
+            let full = register.full();
+            if parts(full).iter().all(|part| self.parts.contains(part)) {
+                if !registers.contains(&full) {
+                    registers.push(full);
+                }
+            } else if self.parts.contains(&register) {
+                registers.push(register);
+            }
+        }
+        registers
+    }
+
+    fn union(&mut self, other: &Live) {
+        self.parts.extend(&other.parts);
+        self.flags.extend(&other.flags);
+    }
+}
+
+impl Display for Live {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts: Vec<String> = self.registers().iter().map(Register::to_string).collect();
+        if !self.flags.is_empty() {
+            let flags: String = self.flags.iter().map(Flag::to_string).collect();
+            parts.push(format!("flags {flags}"));
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// What is live on either side of each instruction, indexed like [`Cfg::lines`].
+pub struct Liveness {
+    pub live_in: Vec<Live>,
+    pub live_out: Vec<Live>,
+}
+
+/// Backward dataflow over `cfg`. Anything might be read once control leaves the
+/// graph, through a `ret` or a jump to somewhere that wasn't decoded, and calls
+/// and unknown instructions are assumed to read everything.
+pub fn liveness(cfg: &Cfg) -> Liveness {
+    let mut live_in = vec![Live::default(); cfg.lines.len()];
+    let mut live_out = vec![Live::default(); cfg.lines.len()];
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+
+        for (index, block) in cfg.blocks.iter().enumerate().rev() {
+            let mut live = if leaves_graph(cfg, index) {
+                Live::all()
+            } else {
+                Live::default()
+            };
+            for edge in cfg.successors(index) {
+                live.union(&live_in[cfg.blocks[edge.to].lines.start]);
+            }
+
+            for line in block.lines.clone().rev() {
+                live_out[line] = live.clone();
+                step_back(&mut live, &cfg.lines[line].instruction);
+
+                if live != live_in[line] {
+                    live_in[line] = live.clone();
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    Liveness { live_in, live_out }
+}
+
+/// Whether `block` can pass control somewhere without an edge for it.
+fn leaves_graph(cfg: &Cfg, block: usize) -> bool {
+    let last = &cfg.lines[cfg.blocks[block].lines.end - 1];
+    let jumps = match last.instruction {
+        Instruction::Call { .. } => None,
+        _ => last.jump_target(),
+    };
+    let expected = jumps.iter().count() + last.fallthrough().iter().count();
+
+    expected == 0 || cfg.successors(block).count() < expected
+}
+
+/// Turns what is live after `instruction` into what is live before it.
+fn step_back(live: &mut Live, instruction: &Instruction) {
+    let writes = effects::writes(instruction);
+    for register in writes.registers {
+        for part in parts(register) {
+            live.parts.remove(part);
+        }
+    }
+    for flag in writes.flags {
+        live.flags.remove(&flag);
+    }
+
+    if matches!(instruction, Instruction::Call { .. } | Instruction::Noop) {
+        live.union(&Live::all());
+        return;
+    }
+
+    let reads = effects::reads(instruction);
+    for register in reads.registers {
+        live.parts.extend(parts(register));
+    }
+    live.flags.extend(reads.flags);
+}
+
+/// An instruction whose only effects are overwritten before anything reads them.
+pub struct DeadStore {
+    pub offset: usize,
+    /// The registers it writes, empty for a compare.
+    pub registers: Vec<Register>,
+}
+
+impl Display for DeadStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.registers.as_slice() {
+            [] => write!(f, "flags are overwritten before anything reads them"),
+            registers => {
+                let names: Vec<String> = registers.iter().map(Register::to_string).collect();
+                write!(f, "{} is overwritten before it is read", names.join(", "))
+            }
+        }
+    }
+}
+
+/// Stores to registers and flags that nothing reads, such as a `mov ax, 1` that
+/// is overwritten before use or a `cmp` whose flags are clobbered before any
+/// jump. Memory writes are never reported, since the analysis can't see reads
+/// from elsewhere.
+pub fn dead_stores(cfg: &Cfg, liveness: &Liveness) -> Vec<DeadStore> {
+    cfg.lines
+        .iter()
+        .enumerate()
+        .filter_map(|(index, decoded)| {
+            let writes = effects::writes(&decoded.instruction);
+            let live = &liveness.live_out[index];
+
+            let has_other_effects = writes.memory
+                || !decoded.instruction.falls_through()
+                || decoded.jump_target().is_some()
+                || matches!(decoded.instruction, Instruction::Noop);
+            let is_read = writes
+                .registers
+                .iter()
+                .any(|register| live.contains(*register))
+                || writes.flags.iter().any(|flag| live.flags.contains(flag));
+
+            (!writes.is_empty() && !has_other_effects && !is_read).then_some(DeadStore {
+                offset: decoded.offset,
+                registers: writes.registers,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode_with_offsets;
+
+    fn dead(bytes: &[u8]) -> Vec<(usize, Vec<Register>)> {
+        let cfg = Cfg::new(decode_with_offsets(bytes));
+        dead_stores(&cfg, &liveness(&cfg))
+            .into_iter()
+            .map(|store| (store.offset, store.registers))
+            .collect()
+    }
+
+    #[test]
+    fn overwritten_register_is_dead() {
+        // mov ax, 1; mov ax, 2; ret
+        let bytes = [0xB8, 0x01, 0x00, 0xB8, 0x02, 0x00, 0xC3];
+
+        assert_eq!(dead(&bytes), vec![(0, vec![Register::AX])]);
+    }
+
+    #[test]
+    fn register_read_before_overwrite_is_live() {
+        // mov ax, 1; add bx, ax; mov ax, 2; ret
+        let bytes = [0xB8, 0x01, 0x00, 0x01, 0xC3, 0xB8, 0x02, 0x00, 0xC3];
+
+        assert!(dead(&bytes).is_empty());
+    }
+
+    #[test]
+    fn writing_one_half_leaves_the_other_live() {
+        // mov ax, 5; mov al, 1; ret
+        let bytes = [0xB8, 0x05, 0x00, 0xB0, 0x01, 0xC3];
+
+        assert!(dead(&bytes).is_empty());
+    }
+
+    #[test]
+    fn flags_clobbered_before_a_branch_are_dead() {
+        // cmp ax, 0; cmp bx, 0; je 8; ret
+        let bytes = [0x83, 0xF8, 0x00, 0x83, 0xFB, 0x00, 0x74, 0x00, 0xC3];
+
+        assert_eq!(dead(&bytes), vec![(0, vec![])]);
+    }
+
+    #[test]
+    fn liveness_flows_around_loops() {
+        // mov cx, 3; add ax, cx; sub cx, 1; jne 3; ret
+        let bytes = [
+            0xB9, 0x03, 0x00, 0x01, 0xC8, 0x83, 0xE9, 0x01, 0x75, 0xF9, 0xC3,
+        ];
+        let cfg = Cfg::new(decode_with_offsets(&bytes));
+        let liveness = liveness(&cfg);
+
+        // After the jne, both the loop and the ret can read CX.
+        assert!(liveness.live_in[1].contains(Register::CX));
+        assert!(liveness.live_out[3].contains(Register::CX));
+        assert!(!liveness.live_in[0].contains(Register::CX));
+        assert!(dead(&bytes).is_empty());
+    }
+}
@@ -0,0 +1,335 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Display},
+    ops::RangeInclusive,
+};
+
+use crate::{
+    cfg::{Cfg, EdgeKind},
+    cycles::{self, Cycles},
+    format::FormatOptions,
+    Decoded,
+};
+
+/// A loop found from its back edges: every block that can reach one of them
+/// without passing through the header, which dominates them all.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NaturalLoop {
+    /// Index of the block every iteration starts with.
+    pub header: usize,
+    /// Blocks with a back edge to the header.
+    pub latches: Vec<usize>,
+    /// Every block in the loop, the header included.
+    pub body: BTreeSet<usize>,
+}
+
+impl NaturalLoop {
+    /// The loop's instructions in address order.
+    pub fn instructions<'a>(&'a self, cfg: &'a Cfg) -> impl Iterator<Item = &'a Decoded> {
+        self.body.iter().flat_map(|block| cfg.instructions(*block))
+    }
+
+    /// Clocks for each instruction in the body. A branch counts as taken when it
+    /// lands inside the loop, so the back edge is taken and exits are not.
+    /// Odd-address penalties depend on runtime addresses and are left out.
+    pub fn cycles(&self, cfg: &Cfg) -> Vec<Cycles> {
+        self.instructions(cfg)
+            .map(|decoded| cycles::estimate(&decoded.instruction, self.stays_inside(cfg, decoded)))
+            .collect()
+    }
+
+    /// The fewest and most clocks one trip from the header back to it can take,
+    /// over every path through the body. Each branch costs what it does for the
+    /// edge the path leaves by. Paths follow no back edge but the loop's own, so
+    /// an inner loop counts as one pass at most.
+    pub fn cycles_per_iteration(&self, cfg: &Cfg) -> RangeInclusive<u32> {
+        let dominators = dominators(cfg);
+        let mut found = BTreeMap::new();
+
+        let (min, max) = self
+            .path_cycles(cfg, &dominators, self.header, &mut found)
+            .unwrap_or_default();
+        min..=max
+    }
+
+    /// Clocks from the start of `block` back to the header, or `None` when the
+    /// only way back goes around an inner loop.
+    fn path_cycles(
+        &self,
+        cfg: &Cfg,
+        dominators: &[BTreeSet<usize>],
+        block: usize,
+        found: &mut BTreeMap<usize, Option<(u32, u32)>>,
+    ) -> Option<(u32, u32)> {
+        if let Some(range) = found.get(&block) {
+            return *range;
+        }
+
+        let (last, rest) = cfg
+            .instructions(block)
+            .split_last()
+            .expect("blocks aren't empty");
+        let straight: u32 = rest
+            .iter()
+            .map(|decoded| cycles::estimate(&decoded.instruction, false).total())
+            .sum();
+
+        let mut range: Option<(u32, u32)> = None;
+        for edge in cfg.successors(block) {
+            let rest = match edge.to {
+                to if to == self.header => Some((0, 0)),
+                to if !self.body.contains(&to) || dominators[block].contains(&to) => None,
+                to => self.path_cycles(cfg, dominators, to, found),
+            };
+            let Some((min, max)) = rest else {
+                continue;
+            };
+
+            let here = straight
+                + cycles::estimate(&last.instruction, edge.kind == EdgeKind::Taken).total();
+            range = Some(match range {
+                Some((low, high)) => (low.min(here + min), high.max(here + max)),
+                None => (here + min, here + max),
+            });
+        }
+
+        found.insert(block, range);
+        range
+    }
+
+    fn stays_inside(&self, cfg: &Cfg, decoded: &Decoded) -> bool {
+        decoded
+            .jump_target()
+            .and_then(|target| cfg.block_at(target))
+            .is_some_and(|block| self.body.contains(&block))
+    }
+}
+
+/// For each block, the blocks that every path to it passes through, itself
+/// included. Paths start at [`Cfg::entries`].
+pub fn dominators(cfg: &Cfg) -> Vec<BTreeSet<usize>> {
+    let all: BTreeSet<usize> = (0..cfg.blocks.len()).collect();
+    let entries = cfg.entries();
+    let is_root = |block: usize| entries.contains(&block);
+
+    let mut dominators: Vec<BTreeSet<usize>> = (0..cfg.blocks.len())
+        .map(|block| match is_root(block) {
+            true => BTreeSet::from([block]),
+            false => all.clone(),
+        })
+        .collect();
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+
+        for block in (0..cfg.blocks.len()).filter(|block| !is_root(*block)) {
+            let mut next = cfg
+                .predecessors(block)
+                .map(|edge| dominators[edge.from].clone())
+                .reduce(|a, b| a.intersection(&b).copied().collect())
+                .unwrap_or_default();
+            next.insert(block);
+
+            if next != dominators[block] {
+                dominators[block] = next;
+                changed = true;
+            }
+        }
+    }
+
+    dominators
+}
+
+/// Every natural loop in `cfg`, ordered by header. Back edges sharing a header
+/// make up one loop.
+pub fn natural_loops(cfg: &Cfg) -> Vec<NaturalLoop> {
+    let dominators = dominators(cfg);
+    let mut loops: Vec<NaturalLoop> = vec![];
+
+    for edge in &cfg.edges {
+        if !dominators[edge.from].contains(&edge.to) {
+            continue;
+        }
+
+        let header = edge.to;
+        let mut body = BTreeSet::from([header]);
+        let mut pending = vec![edge.from];
+        while let Some(block) = pending.pop() {
+            if body.insert(block) {
+                pending.extend(cfg.predecessors(block).map(|edge| edge.from));
+            }
+        }
+
+        match loops.iter_mut().find(|found| found.header == header) {
+            Some(found) => {
+                found.latches.push(edge.from);
+                found.body.extend(body);
+            }
+            None => loops.push(NaturalLoop {
+                header,
+                latches: vec![edge.from],
+                body,
+            }),
+        }
+    }
+
+    loops.sort_by_key(|found| found.header);
+    loops
+}
+
+/// Each loop's instructions with their clocks, and the total per iteration.
+pub struct Loops<'a> {
+    pub cfg: &'a Cfg,
+    pub loops: Vec<NaturalLoop>,
+    pub options: FormatOptions,
+}
+
+impl<'a> Loops<'a> {
+    pub fn new(cfg: &'a Cfg, options: FormatOptions) -> Self {
+        Self {
+            cfg,
+            loops: natural_loops(cfg),
+            options,
+        }
+    }
+}
+
+impl Display for Loops<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cfg = self.cfg;
+
+        if self.loops.is_empty() {
+            return write!(f, "no loops found");
+        }
+
+        for (index, found) in self.loops.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+
+            let latches = found
+                .latches
+                .iter()
+                .map(|latch| {
+                    let last = cfg
+                        .instructions(*latch)
+                        .last()
+                        .expect("blocks aren't empty");
+                    format!("{:04X}", last.offset)
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            let clocks = found.cycles_per_iteration(cfg);
+            let clocks = match clocks.start() == clocks.end() {
+                true => clocks.start().to_string(),
+                false => format!("{} to {}", clocks.start(), clocks.end()),
+            };
+
+            writeln!(
+                f,
+                "loop at {:04X}, back edge from {latches}: {clocks} clocks per iteration",
+                cfg.blocks[found.header].start,
+            )?;
+
+            let lines: Vec<(String, Cycles)> = found
+                .instructions(cfg)
+                .zip(found.cycles(cfg))
+                .map(|(decoded, cycles)| {
+                    let instruction = decoded.instruction.formatted(&self.options);
+                    (format!("{:04X}: {instruction}", decoded.offset), cycles)
+                })
+                .collect();
+            let width = lines.iter().map(|(line, _)| line.len()).max().unwrap_or(0);
+
+            for (line, cycles) in lines {
+                writeln!(f, "    {line:<width$}    ; {cycles}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode_with_offsets;
+
+    fn cfg(bytes: &[u8]) -> Cfg {
+        Cfg::new(decode_with_offsets(bytes))
+    }
+
+    #[test]
+    fn branches_dominate_both_arms_and_the_join() {
+        // cmp ax, 0; je 8; mov bx, 1; ret
+        let cfg = cfg(&[0x83, 0xF8, 0x00, 0x74, 0x03, 0xBB, 0x01, 0x00, 0xC3]);
+        let dominators = dominators(&cfg);
+
+        assert_eq!(dominators[1], BTreeSet::from([0, 1]));
+        assert_eq!(dominators[2], BTreeSet::from([0, 2]));
+        assert!(natural_loops(&cfg).is_empty());
+    }
+
+    #[test]
+    fn back_edge_to_a_dominator_is_a_loop() {
+        // mov cx, 3; add ax, cx; sub cx, 1; jne 3; ret
+        let cfg = cfg(&[
+            0xB9, 0x03, 0x00, 0x01, 0xC8, 0x83, 0xE9, 0x01, 0x75, 0xF9, 0xC3,
+        ]);
+        let loops = natural_loops(&cfg);
+
+        assert_eq!(
+            loops,
+            vec![NaturalLoop {
+                header: 1,
+                latches: vec![1],
+                body: BTreeSet::from([1]),
+            }]
+        );
+        // add reg, reg 3 + sub reg, imm 4 + jne taken 16
+        assert_eq!(loops[0].cycles_per_iteration(&cfg), 23..=23);
+    }
+
+    #[test]
+    fn a_branch_inside_the_loop_gives_a_range() {
+        // 0: mov cx, 3; 3: cmp ax, 0; 6: je 0xb; 8: add bx, 1; 11: sub cx, 1;
+        // 14: jne 3; 16: ret
+        let cfg = cfg(&[
+            0xB9, 0x03, 0x00, 0x83, 0xF8, 0x00, 0x74, 0x03, 0x83, 0xC3, 0x01, 0x83, 0xE9, 0x01,
+            0x75, 0xF3, 0xC3,
+        ]);
+        let loops = Loops::new(&cfg, FormatOptions::default());
+
+        // cmp 4 + je not taken 4 + add 4 + sub 4 + jne taken 16, or
+        // cmp 4 + je taken 16 + sub 4 + jne taken 16
+        assert_eq!(loops.loops[0].cycles_per_iteration(&cfg), 32..=40);
+        assert!(loops
+            .to_string()
+            .starts_with("loop at 0003, back edge from 000E: 32 to 40 clocks per iteration\n"));
+    }
+
+    #[test]
+    fn back_edges_sharing_a_header_make_one_loop() {
+        // 0: add ax, cx; 2: je 0; 4: sub cx, 1; 7: jne 0; 9: ret
+        let cfg = cfg(&[0x01, 0xC8, 0x74, 0xFC, 0x83, 0xE9, 0x01, 0x75, 0xF7, 0xC3]);
+        let loops = natural_loops(&cfg);
+
+        assert_eq!(loops.len(), 1);
+        assert_eq!(loops[0].header, 0);
+        assert_eq!(loops[0].latches, vec![0, 1]);
+        assert_eq!(loops[0].body, BTreeSet::from([0, 1]));
+        // add 3 + je taken 16, or add 3 + je 4 + sub 4 + jne taken 16
+        assert_eq!(loops[0].cycles_per_iteration(&cfg), 19..=27);
+    }
+
+    #[test]
+    fn jump_into_the_middle_of_a_cycle_is_not_a_natural_loop() {
+        // je 4; add ax, cx; sub cx, 1; jne 2; ret. Either block of the cycle
+        // can be entered first, so neither dominates the other.
+        let cfg = cfg(&[0x74, 0x02, 0x01, 0xC8, 0x83, 0xE9, 0x01, 0x75, 0xF9, 0xC3]);
+
+        assert!(natural_loops(&cfg).is_empty());
+    }
+}
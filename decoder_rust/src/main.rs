@@ -1,33 +1,598 @@
-use std::{io, process::Command};
+use std::{
+    fs,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    thread,
+};
 
-use clap::Parser;
-use decoder::{decode, utils::PrintVec};
+use clap::{Parser, Subcommand, ValueEnum};
+use decoder::{
+    batch::{self, BatchOptions, FileReport, Report},
+    boot,
+    cfg::Cfg,
+    constants::{self, Constants},
+    cycles::{self, Cycles},
+    dos,
+    dot::Dot,
+    equivalence::{self, CheckOptions, Verdict},
+    format::{FormatOptions, NumberStyle, SizeStyle, Syntax},
+    functions,
+    hex::{self, Records},
+    image::Image,
+    lint,
+    listing::{Data, Listing, Program, Symbols},
+    liveness,
+    loops::Loops,
+    nasm,
+    peephole::Peephole,
+    sim::{Changes, Cpu, Snapshot, Step},
+    symbolic::Summaries,
+    utils::PrintVec,
+    Decoded,
+};
 
 #[derive(Parser)]
 struct Args {
-    asm: String,
+    #[command(subcommand)]
+    command: Command,
 }
 
-fn main() -> Result<(), io::Error> {
-    let Args { asm } = Args::parse();
+#[derive(Subcommand)]
+enum Command {
+    /// Disassemble the input.
+    Decode {
+        #[command(flatten)]
+        input: Input,
 
-    Command::new("nasm").arg(&asm).output()?;
+        #[command(flatten)]
+        output: Output,
+    },
+    /// Execute the input on a simulated 8086 and print the final registers.
+    Simulate {
+        #[command(flatten)]
+        input: Input,
 
-    let output = match asm.strip_suffix(".asm") {
-        Some(s) => s,
-        None => &asm,
+        #[command(flatten)]
+        run: Run,
+    },
+    /// Execute the input and estimate the 8086 clocks spent on each instruction.
+    Estimate {
+        #[command(flatten)]
+        input: Input,
+
+        #[command(flatten)]
+        run: Run,
+    },
+    /// Assemble an `.asm` file with nasm.
+    Assemble {
+        asm: PathBuf,
+
+        /// Where to write the binary; stdout if omitted.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Check that the disassembly reassembles to the original bytes.
+    Verify {
+        #[command(flatten)]
+        input: Input,
+    },
+    /// Check whether two instruction sequences leave the same registers and memory.
+    Compare {
+        /// `.asm` files to assemble with nasm, or with `--raw` already-assembled binaries.
+        first: PathBuf,
+        second: PathBuf,
+
+        /// Treat the inputs as assembled binaries.
+        #[arg(short, long)]
+        raw: bool,
+
+        /// Don't require the flags to match.
+        #[arg(long)]
+        ignore_flags: bool,
+
+        /// Starting states to run both from when they can't be compared symbolically.
+        #[arg(long, default_value_t = CheckOptions::default().trials)]
+        trials: usize,
+    },
+    /// Decode many files, or every `.asm` file under a directory, and summarize each.
+    Batch {
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+
+        /// Treat inputs as assembled binaries, and include every file under a directory.
+        #[arg(short, long)]
+        raw: bool,
+
+        /// Skip reassembling each disassembly with nasm.
+        #[arg(long)]
+        no_round_trip: bool,
+
+        /// Number of files to work on at once; defaults to the available parallelism.
+        #[arg(short, long)]
+        jobs: Option<usize>,
+    },
+}
+
+#[derive(clap::Args)]
+struct Input {
+    /// An `.asm` file to assemble with nasm, or with `--raw` an already-assembled binary.
+    /// `-` reads a binary from stdin. Hex files are always read as-is.
+    input: String,
+
+    /// Decode the input as-is instead of assembling it first.
+    #[arg(short, long)]
+    raw: bool,
+
+    /// How the bytes are laid out in memory.
+    #[arg(short, long, value_enum, default_value_t)]
+    format: Format,
+
+    /// Segment to place a DOS program's PSP at, in decimal or 0x-prefixed hex.
+    #[arg(long, default_value = "0x1000", value_parser = parse_number)]
+    segment: u16,
+
+    /// Command line arguments to place in the PSP of a DOS program.
+    #[arg(long, default_value = "")]
+    command_tail: String,
+
+    /// Address of the first byte of a flat binary, like nasm's `org`, in decimal
+    /// or 0x-prefixed hex. Other formats know where they load.
+    #[arg(long, value_parser = parse_number)]
+    origin: Option<u16>,
+}
+
+#[derive(Clone, Copy, Default, ValueEnum)]
+enum Format {
+    /// A bare binary starting at address 0.
+    #[default]
+    Flat,
+    /// A DOS .COM program, loaded at CS:0100 behind its PSP.
+    Com,
+    /// A DOS MZ executable, relocated to load right after its PSP.
+    Exe,
+    /// A 512-byte PC boot sector, loaded at 0000:7C00.
+    Boot,
+    /// Intel HEX records, each loaded at the address it names.
+    Ihex,
+    /// Motorola S-records, each loaded at the address it names.
+    Srec,
+}
+
+impl Input {
+    fn load(&self) -> Result<Image, io::Error> {
+        let bytes = self.read()?;
+
+        if self.origin.is_some() && !matches!(self.format, Format::Flat) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "--origin only applies to flat binaries",
+            ));
+        }
+
+        match self.format {
+            Format::Flat => Ok(Image::flat(bytes, self.origin.unwrap_or(0))),
+            Format::Com => dos::load_com(&bytes, self.segment, &self.command_tail),
+            Format::Exe => dos::load_exe(&bytes, self.segment, &self.command_tail),
+            Format::Boot => boot::load_boot_sector(&bytes),
+            Format::Ihex => Ok(report_gaps(hex::parse_intel_hex(&text(bytes)?)?).to_image()),
+            Format::Srec => Ok(report_gaps(hex::parse_srec(&text(bytes)?)?).to_image()),
+        }
+    }
+
+    fn read(&self) -> Result<Vec<u8>, io::Error> {
+        if self.input == "-" {
+            let mut bytes = vec![];
+            io::stdin().read_to_end(&mut bytes)?;
+            Ok(bytes)
+        } else if self.raw || matches!(self.format, Format::Ihex | Format::Srec) {
+            fs::read(&self.input)
+        } else {
+            nasm::assemble(Path::new(&self.input))
+        }
+    }
+}
+
+fn text(bytes: Vec<u8>) -> Result<String, io::Error> {
+    String::from_utf8(bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// Notes any holes between records on stderr, so a listing that jumps from one
+/// address to another doesn't hide missing data.
+fn report_gaps(records: Records) -> Records {
+    for gap in records.gaps() {
+        eprintln!(
+            "gap: {:05X}-{:05X} ({} bytes) not covered by any record",
+            gap.start,
+            gap.end - 1,
+            gap.len()
+        );
+    }
+
+    records
+}
+
+#[derive(clap::Args)]
+struct Output {
+    /// Print each instruction with its address and encoded bytes.
+    #[arg(short, long)]
+    listing: bool,
+
+    /// Replace jump increments with labels so the output reassembles as-is.
+    #[arg(long, conflicts_with = "listing")]
+    labels: bool,
+
+    /// Annotate the listing with warnings about stores nothing reads, code nothing
+    /// reaches, and addressing and jump forms that cost more than they need to.
+    #[arg(short, long, requires = "listing")]
+    warnings: bool,
+
+    /// Annotate the listing with register values and addresses known without
+    /// running the program.
+    #[arg(short, long, requires = "listing")]
+    constants: bool,
+
+    /// Print the basic blocks as a Graphviz DOT graph instead of a listing.
+    #[arg(long, conflicts_with_all = ["listing", "labels"])]
+    dot: bool,
+
+    /// Print each loop's instructions and estimated clocks per iteration.
+    #[arg(long, conflicts_with_all = ["listing", "labels", "dot"])]
+    loops: bool,
+
+    /// Print what each basic block leaves in registers, memory and flags, in
+    /// terms of their values before it ran.
+    #[arg(long, conflicts_with_all = ["listing", "labels", "dot", "loops"])]
+    symbolic: bool,
+
+    /// Suggest cheaper instructions for wasteful patterns, with the clocks and
+    /// bytes each would save.
+    #[arg(long, conflicts_with_all = ["listing", "labels", "dot", "loops", "symbolic"])]
+    peephole: bool,
+
+    /// Find functions from calls and `push bp; mov bp, sp` prologues, label
+    /// them, and name their `[bp - n]` locals and `[bp + n]` arguments.
+    #[arg(long, conflicts_with_all = ["dot", "loops", "symbolic", "peephole"])]
+    functions: bool,
+
+    #[command(flatten)]
+    style: Style,
+
+    /// Only decode what control flow reaches from the entry point, printing the
+    /// rest as data, instead of sweeping through every byte.
+    #[arg(short, long)]
+    traverse: bool,
+
+    /// An extra address to traverse from, in decimal or 0x-prefixed hex. Repeatable.
+    #[arg(long, requires = "traverse", value_parser = parse_number)]
+    entry: Vec<u16>,
+}
+
+#[derive(clap::Args)]
+struct Style {
+    /// Assembly syntax to print instructions in.
+    #[arg(short, long, value_enum, default_value_t)]
+    syntax: Syntax,
+
+    /// How to write immediates, displacements and addresses.
+    #[arg(long, value_enum, default_value_t)]
+    numbers: NumberStyle,
+
+    /// Write mnemonics, registers and size keywords in upper case.
+    #[arg(long)]
+    uppercase: bool,
+
+    /// How to size memory operands. MASM syntax always uses `word ptr`.
+    #[arg(long, value_enum, default_value_t)]
+    sizes: SizeStyle,
+
+    /// Size every memory operand, even when a register operand already implies it.
+    #[arg(long)]
+    explicit_sizes: bool,
+
+    /// Separate the mnemonic from its operands with a tab instead of a space.
+    #[arg(long)]
+    align_operands: bool,
+
+    /// Write `[bx+si+4]` rather than `[bx + si + 4]`.
+    #[arg(long)]
+    compact_operators: bool,
+}
+
+impl Style {
+    fn options(&self) -> FormatOptions {
+        FormatOptions {
+            syntax: self.syntax,
+            numbers: self.numbers,
+            uppercase: self.uppercase,
+            sizes: self.sizes,
+            explicit_sizes: self.explicit_sizes,
+            align_operands: self.align_operands,
+            spaced_operators: !self.compact_operators,
+        }
+    }
+}
+
+#[derive(clap::Args)]
+struct Run {
+    /// IP to start executing from, in decimal or 0x-prefixed hex; the program's
+    /// entry point if omitted.
+    #[arg(long, value_parser = parse_number)]
+    start: Option<u16>,
+
+    /// Stop after this many instructions, in case the program never leaves its code.
+    #[arg(long, default_value_t = 100_000)]
+    max_steps: usize,
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run() -> Result<ExitCode, io::Error> {
+    let Args { command } = Args::parse();
+
+    match command {
+        Command::Decode { input, output } => decode(&input.load()?, output),
+        Command::Simulate { input, run } => {
+            let cpu = simulate(&input.load()?, run, |before, step, after| {
+                let changes = Changes {
+                    before: *before,
+                    after: after.snapshot(),
+                };
+                println!("{} ; {changes}", step.decoded.instruction);
+            });
+
+            println!("\n{cpu}");
+        }
+        Command::Estimate { input, run } => {
+            let mut total = 0;
+
+            simulate(&input.load()?, run, |before, step, _| {
+                let cycles = estimate(before, step);
+                total += cycles.total();
+                println!("{} ; Clocks: +{cycles} = {total}", step.decoded.instruction);
+            });
+
+            println!("\nTotal clocks: {total}");
+        }
+        Command::Assemble { asm, output } => {
+            let bytes = nasm::assemble(&asm)?;
+            match output {
+                Some(output) => fs::write(output, bytes)?,
+                None => io::stdout().write_all(&bytes)?,
+            }
+        }
+        Command::Verify { input } => {
+            let bytes = input.read()?;
+            match nasm::round_trip(&bytes)? {
+                None => println!("ok: {} bytes round-trip", bytes.len()),
+                Some(offset) => {
+                    println!("mismatch at {offset:#06x}");
+                    return Ok(ExitCode::FAILURE);
+                }
+            }
+        }
+        Command::Compare {
+            first,
+            second,
+            raw,
+            ignore_flags,
+            trials,
+        } => {
+            let read = |path: &Path| match raw {
+                true => fs::read(path),
+                false => nasm::assemble(path),
+            };
+            let first = decoder::decode_with_offsets(&read(&first)?);
+            let second = decoder::decode_with_offsets(&read(&second)?);
+            let options = CheckOptions {
+                flags: !ignore_flags,
+                trials,
+                ..Default::default()
+            };
+
+            let verdict = equivalence::check(&first, &second, options)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+            println!("{verdict}");
+
+            if let Verdict::Differs(_) = verdict {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        Command::Batch {
+            inputs,
+            raw,
+            no_round_trip,
+            jobs,
+        } => {
+            let files = batch::collect_inputs(&inputs, raw)?;
+            let options = BatchOptions {
+                raw,
+                round_trip: !no_round_trip,
+                jobs: jobs.unwrap_or_else(|| {
+                    thread::available_parallelism().map_or(1, |jobs| jobs.get())
+                }),
+            };
+
+            let reports = batch::run(&files, options);
+            println!("{}", Report(&reports));
+
+            if !reports.iter().all(FileReport::is_ok) {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn decode(image: &Image, output: Output) {
+    let Output {
+        listing,
+        labels,
+        warnings,
+        constants,
+        dot,
+        loops,
+        symbolic,
+        peephole,
+        functions,
+        style,
+        traverse,
+        entry,
+    } = output;
+
+    let options = style.options();
+
+    let entries: Vec<usize> = entry.into_iter().map(usize::from).collect();
+    let (lines, data) = if traverse {
+        let traversal = image.traverse(&entries);
+        let data: Vec<_> = image.data.iter().cloned().chain(traversal.data).collect();
+        (traversal.lines, data)
+    } else {
+        (image.decode(), image.data.clone())
     };
+    let data = Data::from_ranges(image.code_bytes(), &data);
+
+    let (lines, symbols, notes) = match functions {
+        true => find_functions(lines),
+        false => (lines, Symbols::default(), vec![]),
+    };
+
+    if dot {
+        println!("{}", Dot::new(&Cfg::new(lines), options));
+    } else if loops {
+        println!("{}", Loops::new(&Cfg::new(lines), options));
+    } else if symbolic {
+        println!("{}", Summaries::new(&Cfg::new(lines), options));
+    } else if peephole {
+        println!("{}", Peephole::new(&Cfg::new(lines), options));
+    } else if listing {
+        let (lines, comments) = match warnings || constants {
+            true => {
+                let entries: Vec<usize> =
+                    Some(image.ip as usize).into_iter().chain(entries).collect();
+                analyze(lines, &entries, warnings, constants)
+            }
+            false => (lines, vec![]),
+        };
+        let listing = Listing::new(image.code_bytes(), lines)
+            .with_options(options)
+            .with_data(data)
+            .with_comments(notes)
+            .with_comments(comments)
+            .with_symbols(symbols);
+        println!("{listing}");
+    } else if labels {
+        let program = Program::new(lines, options)
+            .with_data(data)
+            .with_symbols(symbols);
+        println!("{program}");
+    } else {
+        let instructions = lines
+            .iter()
+            .map(|decoded| symbols.formatted(decoded, &options))
+            .collect();
+
+        println!("{}", PrintVec(instructions));
+    }
+}
+
+/// Functions in `lines`, as names for the output and notes for the listing.
+fn find_functions(lines: Vec<Decoded>) -> (Vec<Decoded>, Symbols, Vec<(usize, String)>) {
+    let cfg = Cfg::new(lines);
+    let found = functions::functions(&cfg);
+    let symbols = functions::symbols(&cfg, &found);
+    (cfg.lines, symbols, functions::annotations(&found))
+}
+
+/// Notes about `lines` for the listing, keyed by offset. Control is assumed to
+/// arrive from outside only at `entries` and call targets.
+fn analyze(
+    lines: Vec<Decoded>,
+    entries: &[usize],
+    warnings: bool,
+    constants: bool,
+) -> (Vec<Decoded>, Vec<(usize, String)>) {
+    let cfg = Cfg::new(lines);
+    let mut comments = vec![];
 
-    let bytes = std::fs::read(output).unwrap();
+    if constants {
+        let propagation = constants::propagate(&cfg, &Constants::unknown());
+        comments.extend(constants::annotations(&cfg, &propagation));
+    }
 
-    let instructions = decode(bytes);
+    if warnings {
+        let liveness = liveness::liveness(&cfg);
+        comments.extend(
+            liveness::dead_stores(&cfg, &liveness)
+                .into_iter()
+                .map(|store| (store.offset, format!("warning: {store}"))),
+        );
+        comments.extend(
+            lint::lint(&cfg, entries)
+                .into_iter()
+                .map(|warning| (warning.offset, format!("warning: {warning}"))),
+        );
+    }
 
-    #[cfg(debug_assertions)]
-    println!();
+    (cfg.lines, comments)
+}
+
+/// Runs `image` from its entry point or `run.start` until IP leaves the code or
+/// `run.max_steps` is hit, handing each step to `on_step` along with the registers
+/// before it and the CPU after it.
+fn simulate(image: &Image, run: Run, mut on_step: impl FnMut(&Snapshot, &Step, &Cpu)) -> Cpu {
+    let mut cpu = image.load();
+    if let Some(start) = run.start {
+        cpu.ip = start;
+    }
+
+    let mut steps = 0;
 
-    println!("{}", PrintVec(instructions));
+    while image.is_code(&cpu) {
+        if steps == run.max_steps {
+            eprintln!("stopped after {steps} steps");
+            break;
+        }
 
-    Command::new("rm").arg(output).output()?;
+        let before = cpu.snapshot();
+        let step = cpu.step();
+        on_step(&before, &step, &cpu);
+
+        steps += 1;
+    }
+
+    cpu
+}
+
+fn estimate(before: &Snapshot, step: &Step) -> Cycles {
+    let instruction = &step.decoded.instruction;
+    let mut cycles = cycles::estimate(instruction, step.taken);
+
+    let odd = instruction
+        .memory_operand()
+        .and_then(|location| before.effective_address(location))
+        .is_some_and(|address| address % 2 == 1);
+
+    if odd {
+        cycles.penalty = 4 * cycles::word_transfers(instruction);
+    }
+
+    cycles
+}
 
-    Ok(())
+fn parse_number(value: &str) -> Result<u16, String> {
+    match value.strip_prefix("0x") {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => value.parse(),
+    }
+    .map_err(|err| err.to_string())
 }
@@ -0,0 +1,154 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    decode_with_offsets,
+    format::FormatOptions,
+    instruction::Instruction,
+    listing::{Data, Program},
+};
+
+/// Assembles `asm` with nasm and returns the flat binary it produced.
+///
+/// nasm writes into a private temporary directory rather than next to the source,
+/// so an existing file that happens to share the output name is never touched.
+pub fn assemble(asm: &Path) -> Result<Vec<u8>, io::Error> {
+    let dir = TempDir::new()?;
+    let output = dir.path().join("out.bin");
+
+    let result = Command::new("nasm")
+        .arg("-f")
+        .arg("bin")
+        .arg("-o")
+        .arg(&output)
+        .arg(asm)
+        .output()
+        .map_err(|err| {
+            io::Error::new(
+                err.kind(),
+                format!("failed to run nasm on {}: {err}", asm.display()),
+            )
+        })?;
+
+    if !result.status.success() {
+        return Err(io::Error::other(format!(
+            "nasm failed on {} ({}):\n{}",
+            asm.display(),
+            result.status,
+            String::from_utf8_lossy(&result.stderr).trim_end(),
+        )));
+    }
+
+    fs::read(&output)
+}
+
+/// Assembles `source` as though it had been read from a file.
+pub fn assemble_source(source: &str) -> Result<Vec<u8>, io::Error> {
+    let dir = TempDir::new()?;
+    let asm = dir.path().join("in.asm");
+
+    fs::write(&asm, source)?;
+
+    assemble(&asm)
+}
+
+/// Renders `bytes` back to nasm source, reassembles it and returns the first
+/// offset where the result differs from `bytes`, if any.
+pub fn round_trip(bytes: &[u8]) -> Result<Option<usize>, io::Error> {
+    let reassembled = assemble_source(&source(bytes))?;
+
+    let mismatch = bytes
+        .iter()
+        .zip(reassembled.iter())
+        .position(|(original, reassembled)| original != reassembled);
+
+    Ok(mismatch
+        .or_else(|| (bytes.len() != reassembled.len()).then(|| bytes.len().min(reassembled.len()))))
+}
+
+/// nasm source for `bytes`. Bytes that didn't decode go out as `db` so they
+/// reassemble unchanged and any mismatch shows up at its offset instead.
+fn source(bytes: &[u8]) -> String {
+    let (noops, lines): (Vec<_>, Vec<_>) = decode_with_offsets(bytes)
+        .into_iter()
+        .partition(|decoded| matches!(decoded.instruction, Instruction::Noop));
+
+    let data = noops
+        .iter()
+        .map(|decoded| Data {
+            offset: decoded.offset,
+            bytes: &bytes[decoded.offset..decoded.offset + decoded.size],
+        })
+        .collect();
+
+    let program = Program::new(lines, FormatOptions::default()).with_data(data);
+    format!("bits 16\n\n{program}")
+}
+
+/// A directory only this process writes to, removed again on drop.
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new() -> Result<Self, io::Error> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.subsec_nanos())
+            .unwrap_or_default();
+
+        loop {
+            let path = std::env::temp_dir().join(format!(
+                "decoder-{}-{}-{nanos}",
+                std::process::id(),
+                COUNTER.fetch_add(1, Ordering::Relaxed),
+            ));
+
+            match create_private_dir(&path) {
+                Ok(()) => return Ok(Self(path)),
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+#[cfg(unix)]
+fn create_private_dir(path: &Path) -> Result<(), io::Error> {
+    use std::os::unix::fs::DirBuilderExt;
+
+    fs::DirBuilder::new().mode(0o700).create(path)
+}
+
+#[cfg(not(unix))]
+fn create_private_dir(path: &Path) -> Result<(), io::Error> {
+    fs::DirBuilder::new().create(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn undecoded_bytes_become_data() {
+        // mov ax, 1; not ax; ret
+        let source = source(&[0xB8, 0x01, 0x00, 0xF7, 0xD0, 0xC3]);
+
+        assert_eq!(source, "bits 16\n\nmov ax, 1\ndb 247, 208\nret\n");
+    }
+}
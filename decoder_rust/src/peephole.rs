@@ -0,0 +1,453 @@
+use std::fmt::{self, Display};
+
+use crate::{
+    cfg::Cfg,
+    constants::{self, Constants},
+    cycles,
+    effects::{self, Flag},
+    format::{FormatOptions, Syntax},
+    instruction::{Instruction, Location, Operands, Register, Width},
+    liveness,
+    sim::{immediate_value, mask},
+    symbolic::{Expr, State},
+    Decoded,
+};
+
+/// A cheaper stand-in for one instruction, with its clocks and size from the
+/// Intel tables. Each assembles to something the decoder reads back, so
+/// `compare` can check a rewrite against the original.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Replacement {
+    Xor(Register),
+    Inc(Register),
+    Dec(Register),
+    Test(Register),
+    Remove,
+}
+
+impl Replacement {
+    pub fn cycles(&self) -> u32 {
+        match self {
+            Replacement::Xor(_) | Replacement::Test(_) => 3,
+            Replacement::Inc(register) | Replacement::Dec(register) => match register.width() {
+                Width::Byte => 3,
+                Width::Word => 2,
+            },
+            Replacement::Remove => 0,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        match self {
+            Replacement::Xor(_) | Replacement::Test(_) => 2,
+            Replacement::Inc(register) | Replacement::Dec(register) => match register.width() {
+                Width::Byte => 2,
+                Width::Word => 1,
+            },
+            Replacement::Remove => 0,
+        }
+    }
+
+    pub fn formatted(&self, options: &FormatOptions) -> String {
+        let (mnemonic, register, operands) = match self {
+            Replacement::Xor(register) => ("xor", *register, 2),
+            Replacement::Test(register) => ("test", *register, 2),
+            Replacement::Inc(register) => ("inc", *register, 1),
+            Replacement::Dec(register) => ("dec", *register, 1),
+            Replacement::Remove => return "(remove)".to_string(),
+        };
+
+        let separator = if options.align_operands { "\t" } else { " " };
+        let (mnemonic, register) = match options.syntax {
+            Syntax::Att => {
+                let suffix = match register.width() {
+                    Width::Byte => "b",
+                    Width::Word => "w",
+                };
+                (
+                    format!("{mnemonic}{suffix}"),
+                    format!("%{}", options.keyword(&register.to_string())),
+                )
+            }
+            Syntax::Nasm | Syntax::Masm => {
+                (mnemonic.to_string(), options.keyword(&register.to_string()))
+            }
+        };
+
+        let operands = vec![register; operands].join(", ");
+        format!("{}{separator}{operands}", options.keyword(&mnemonic))
+    }
+}
+
+/// A rewrite of the instruction at `offset` that does the same job for less.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Suggestion {
+    pub offset: usize,
+    pub replacement: Replacement,
+    pub cycles: u32,
+    pub bytes: usize,
+    /// Why the rewrite is safe here.
+    pub reason: String,
+}
+
+/// Rewrites for known-wasteful patterns in `cfg`, at most one per instruction:
+///
+/// - A `mov` goes when its destination already holds the value, or when the
+///   register it writes is overwritten before anything reads it.
+/// - `mov reg, 0` becomes `xor reg, reg` where nothing reads the flags after.
+/// - `add reg, 1` and `sub reg, 1` become `inc` and `dec` where nothing reads
+///   the carry, which those leave alone. Adding or subtracting -1 instead also
+///   needs the auxiliary carry dead, since it comes out the other way round.
+/// - `cmp reg, 0` becomes `test reg, reg` where nothing reads the auxiliary
+///   carry, which `test` leaves undefined.
+///
+/// Only rewrites that save clocks or bytes without costing either are kept.
+pub fn suggest(cfg: &Cfg) -> Vec<Suggestion> {
+    let liveness = liveness::liveness(cfg);
+    let redundant = redundant_moves(cfg);
+
+    // A move that only looks dead because a later one rewrites the same value
+    // has to stay if that later one goes.
+    let relied_on: Vec<Register> = cfg
+        .lines
+        .iter()
+        .filter(|decoded| redundant.contains(&decoded.offset))
+        .flat_map(|decoded| effects::writes(&decoded.instruction).registers)
+        .map(|register| register.full())
+        .collect();
+    let dead: Vec<usize> = liveness::dead_stores(cfg, &liveness)
+        .into_iter()
+        .filter(|store| {
+            let is_mov = cfg.lines.iter().any(|decoded| {
+                decoded.offset == store.offset
+                    && matches!(
+                        decoded.instruction,
+                        Instruction::Mov { .. } | Instruction::MovImmediate { .. }
+                    )
+            });
+            is_mov
+                && store
+                    .registers
+                    .iter()
+                    .all(|register| !relied_on.contains(&register.full()))
+        })
+        .map(|store| store.offset)
+        .collect();
+
+    let mut suggestions = vec![];
+
+    for (index, decoded) in cfg.lines.iter().enumerate() {
+        let live = &liveness.live_out[index];
+        let instruction = &decoded.instruction;
+
+        let rewrite = if redundant.contains(&decoded.offset) {
+            Some((
+                Replacement::Remove,
+                "the destination already holds the value",
+            ))
+        } else if dead.contains(&decoded.offset) {
+            Some((
+                Replacement::Remove,
+                "the destination is overwritten before it is read",
+            ))
+        } else if let Operands::Immediate { data, dest } = instruction.operands() {
+            let width = dest.width().unwrap_or(data.width());
+            let value = immediate_value(data) & mask(width);
+
+            match (instruction, dest.register.filter(|_| !dest.is_mem_addr)) {
+                (Instruction::MovImmediate { .. }, Some(register))
+                    if value == 0 && live.flags.is_empty() =>
+                {
+                    Some((Replacement::Xor(register), "no flags are live"))
+                }
+                (
+                    Instruction::AddImmediate { .. } | Instruction::SubImmediate { .. },
+                    Some(register),
+                ) if value == 1 && !live.flags.contains(&Flag::Carry) => {
+                    let replacement = match instruction {
+                        Instruction::AddImmediate { .. } => Replacement::Inc(register),
+                        _ => Replacement::Dec(register),
+                    };
+                    Some((replacement, "the carry isn't live"))
+                }
+                (
+                    Instruction::AddImmediate { .. } | Instruction::SubImmediate { .. },
+                    Some(register),
+                ) if value == mask(width)
+                    && !live.flags.contains(&Flag::Carry)
+                    && !live.flags.contains(&Flag::Auxiliary) =>
+                {
+                    let replacement = match instruction {
+                        Instruction::AddImmediate { .. } => Replacement::Dec(register),
+                        _ => Replacement::Inc(register),
+                    };
+                    Some((
+                        replacement,
+                        "neither the carry nor the auxiliary carry is live",
+                    ))
+                }
+                (Instruction::CmpImmediate { .. }, Some(register))
+                    if value == 0 && !live.flags.contains(&Flag::Auxiliary) =>
+                {
+                    Some((
+                        Replacement::Test(register),
+                        "the auxiliary carry isn't live",
+                    ))
+                }
+                _ => None,
+            }
+        } else {
+            None
+        };
+
+        let Some((replacement, reason)) = rewrite else {
+            continue;
+        };
+
+        let before = cycles::estimate(instruction, false).total();
+        let (after, size) = (replacement.cycles(), replacement.size());
+        if before < after || decoded.size < size || (before, decoded.size) == (after, size) {
+            continue;
+        }
+
+        suggestions.push(Suggestion {
+            offset: decoded.offset,
+            replacement,
+            cycles: before - after,
+            bytes: decoded.size - size,
+            reason: reason.to_string(),
+        });
+    }
+
+    suggestions
+}
+
+/// Offsets of moves whose destination already holds what they would write,
+/// going by symbolic evaluation within each block and known constants across
+/// blocks. Calls and undecoded instructions could change anything, so the
+/// evaluation starts over after them.
+fn redundant_moves(cfg: &Cfg) -> Vec<usize> {
+    let propagation = constants::propagate(cfg, &Constants::unknown());
+    let mut redundant = vec![];
+
+    for (index, block) in cfg.blocks.iter().enumerate() {
+        let mut state = State::initial();
+
+        for (line, decoded) in block.lines.clone().zip(cfg.instructions(index)) {
+            if already_holds(&state, decoded) || is_known(&propagation.before[line], decoded) {
+                redundant.push(decoded.offset);
+            }
+
+            let unknown_effects = matches!(decoded.instruction, Instruction::Call { .. });
+            if state.execute(decoded).is_err() || unknown_effects {
+                state = State::initial();
+            }
+        }
+    }
+
+    redundant
+}
+
+/// Whether `decoded` moves a constant into a register already known to hold it.
+fn is_known(constants: &Constants, decoded: &Decoded) -> bool {
+    let Instruction::MovImmediate { data, dest } = &decoded.instruction else {
+        return false;
+    };
+    let width = dest.width().unwrap_or(data.width());
+
+    match dest.register {
+        Some(register) if !dest.is_mem_addr => {
+            constants.get(register) == Some(immediate_value(data) & mask(width))
+        }
+        _ => false,
+    }
+}
+
+fn already_holds(state: &State, decoded: &Decoded) -> bool {
+    let (dest, value): (&Location, Expr) = match &decoded.instruction {
+        Instruction::Mov { src, dest } => {
+            let width = dest.width().or(src.width()).unwrap_or(Width::Word);
+            (dest, state.read(src, width))
+        }
+        Instruction::MovImmediate { data, dest } => {
+            let width = dest.width().unwrap_or(data.width());
+            (dest, Expr::constant(immediate_value(data), width))
+        }
+        _ => return false,
+    };
+
+    state.read(dest, value.width()) == value
+}
+
+/// The suggested rewrites next to the instructions they replace, with totals.
+pub struct Peephole<'a> {
+    pub lines: &'a [Decoded],
+    pub suggestions: Vec<Suggestion>,
+    pub options: FormatOptions,
+}
+
+impl<'a> Peephole<'a> {
+    pub fn new(cfg: &'a Cfg, options: FormatOptions) -> Self {
+        Self {
+            lines: &cfg.lines,
+            suggestions: suggest(cfg),
+            options,
+        }
+    }
+}
+
+impl Display for Peephole<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.suggestions.is_empty() {
+            return write!(f, "no rewrites found");
+        }
+
+        let rows: Vec<(String, &Suggestion)> = self
+            .suggestions
+            .iter()
+            .map(|suggestion| {
+                let decoded = self
+                    .lines
+                    .iter()
+                    .find(|decoded| decoded.offset == suggestion.offset)
+                    .expect("suggestions refer to decoded lines");
+                let original = decoded.instruction.formatted(&self.options);
+                let replacement = suggestion.replacement.formatted(&self.options);
+                (
+                    format!("{:04X}: {original} -> {replacement}", suggestion.offset),
+                    suggestion,
+                )
+            })
+            .collect();
+        let width = rows.iter().map(|(row, _)| row.len()).max().unwrap_or(0);
+
+        for (row, suggestion) in rows {
+            writeln!(
+                f,
+                "{row:<width$}    ; saves {} clocks, {} bytes: {}",
+                suggestion.cycles, suggestion.bytes, suggestion.reason
+            )?;
+        }
+
+        let cycles: u32 = self
+            .suggestions
+            .iter()
+            .map(|suggestion| suggestion.cycles)
+            .sum();
+        let bytes: usize = self
+            .suggestions
+            .iter()
+            .map(|suggestion| suggestion.bytes)
+            .sum();
+        write!(
+            f,
+            "{} rewrites save {cycles} clocks and {bytes} bytes",
+            self.suggestions.len()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode_with_offsets;
+
+    fn suggestions(bytes: &[u8]) -> Vec<(usize, Replacement)> {
+        suggest(&Cfg::new(decode_with_offsets(bytes)))
+            .into_iter()
+            .map(|suggestion| (suggestion.offset, suggestion.replacement))
+            .collect()
+    }
+
+    #[test]
+    fn a_byte_store_is_not_the_byte_after_it() {
+        // mov byte [bx], 7; mov al, [bx + 1]; mov al, 0; ret. The load is dead,
+        // but al isn't known to be 0 after it, so the mov al, 0 stays.
+        assert_eq!(
+            suggestions(&[0xC6, 0x07, 0x07, 0x8A, 0x47, 0x01, 0xB0, 0x00, 0xC3]),
+            vec![(3, Replacement::Remove)]
+        );
+    }
+
+    #[test]
+    fn a_move_of_what_is_already_there_goes() {
+        // mov ax, bx; mov ax, bx; ret
+        assert_eq!(
+            suggestions(&[0x89, 0xD8, 0x89, 0xD8, 0xC3]),
+            vec![(2, Replacement::Remove)]
+        );
+
+        // mov ax, bx; mov bx, cx; mov ax, bx; ret
+        let kept = suggestions(&[0x89, 0xD8, 0x89, 0xCB, 0x89, 0xD8, 0xC3]);
+        assert!(!kept.contains(&(4, Replacement::Remove)));
+    }
+
+    #[test]
+    fn a_move_overwritten_before_it_is_read_goes() {
+        // mov ax, 1; mov ax, 2; ret
+        assert_eq!(
+            suggestions(&[0xB8, 0x01, 0x00, 0xB8, 0x02, 0x00, 0xC3]),
+            vec![(0, Replacement::Remove)]
+        );
+
+        // mov ax, 1; add bx, ax; mov ax, 2; ret
+        assert_eq!(
+            suggestions(&[0xB8, 0x01, 0x00, 0x01, 0xC3, 0xB8, 0x02, 0x00, 0xC3]),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn zeroing_becomes_xor_only_where_the_flags_are_dead() {
+        // mov ax, 0; cmp ax, 1; ret
+        assert_eq!(
+            suggestions(&[0xB8, 0x00, 0x00, 0x83, 0xF8, 0x01, 0xC3]),
+            vec![(0, Replacement::Xor(Register::AX))]
+        );
+
+        // mov ax, 0; ret
+        assert_eq!(suggestions(&[0xB8, 0x00, 0x00, 0xC3]), vec![]);
+    }
+
+    #[test]
+    fn adding_one_becomes_inc_only_where_the_carry_is_dead() {
+        // add ax, 1; cmp ax, 5; ret
+        assert_eq!(
+            suggestions(&[0x83, 0xC0, 0x01, 0x83, 0xF8, 0x05, 0xC3]),
+            vec![(0, Replacement::Inc(Register::AX))]
+        );
+
+        // sub cx, 1; inc bx; ret, where the carry survives the inc
+        assert_eq!(suggestions(&[0x83, 0xE9, 0x01, 0x43, 0xC3]), vec![]);
+    }
+
+    #[test]
+    fn adding_minus_one_also_needs_the_auxiliary_carry_dead() {
+        // add ax, -1; cmp ax, 5; ret
+        assert_eq!(
+            suggestions(&[0x83, 0xC0, 0xFF, 0x83, 0xF8, 0x05, 0xC3]),
+            vec![(0, Replacement::Dec(Register::AX))]
+        );
+
+        // add ax, -1; ret
+        assert_eq!(suggestions(&[0x83, 0xC0, 0xFF, 0xC3]), vec![]);
+    }
+
+    #[test]
+    fn comparing_with_zero_becomes_test_where_the_auxiliary_carry_is_dead() {
+        // cmp ax, 0; je 5; cmp bx, 1; ret
+        assert_eq!(
+            suggestions(&[0x83, 0xF8, 0x00, 0x74, 0x00, 0x83, 0xFB, 0x01, 0xC3]),
+            vec![(0, Replacement::Test(Register::AX))]
+        );
+
+        // cmp ax, 0; je 5; ret
+        assert_eq!(suggestions(&[0x83, 0xF8, 0x00, 0x74, 0x00, 0xC3]), vec![]);
+    }
+
+    #[test]
+    fn a_load_of_a_stored_pointer_register_is_left_alone() {
+        // mov [bx], sp; mov al, [bx]; ret
+        assert_eq!(suggestions(&[0x89, 0x27, 0x8A, 0x07, 0xC3]), vec![]);
+    }
+}
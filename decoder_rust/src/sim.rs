@@ -0,0 +1,671 @@
+use std::fmt::{self, Display};
+
+use crate::{
+    decode_at,
+    instruction::{Immediate, Instruction, Location, Operands, Register, Width},
+    Decoded,
+};
+
+pub const MEMORY_SIZE: usize = 1 << 20;
+
+const WORD_REGISTERS: [Register; 8] = [
+    Register::AX,
+    Register::BX,
+    Register::CX,
+    Register::DX,
+    Register::SP,
+    Register::BP,
+    Register::SI,
+    Register::DI,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Segment {
+    ES,
+    CS,
+    SS,
+    DS,
+}
+
+impl Display for Segment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", format!("{:?}", self).to_lowercase())
+    }
+}
+
+const SEGMENTS: [Segment; 4] = [Segment::ES, Segment::CS, Segment::SS, Segment::DS];
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Flags {
+    pub carry: bool,
+    pub parity: bool,
+    pub auxiliary: bool,
+    pub zero: bool,
+    pub sign: bool,
+    pub overflow: bool,
+}
+
+impl Display for Flags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Flags {
+            carry,
+            parity,
+            auxiliary,
+            zero,
+            sign,
+            overflow,
+        } = *self;
+
+        for (set, letter) in [
+            (carry, 'C'),
+            (parity, 'P'),
+            (auxiliary, 'A'),
+            (zero, 'Z'),
+            (sign, 'S'),
+            (overflow, 'O'),
+        ] {
+            if set {
+                write!(f, "{letter}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The outcome of executing one instruction.
+pub struct Step {
+    pub decoded: Decoded,
+    /// Whether a jump, loop, call or return transferred control; false for everything else.
+    pub taken: bool,
+}
+
+#[derive(Clone)]
+pub struct Cpu {
+    registers: [u16; 8],
+    segments: [u16; 4],
+    pub flags: Flags,
+    pub ip: u16,
+    pub memory: Vec<u8>,
+}
+
+impl Default for Cpu {
+    fn default() -> Self {
+        Self {
+            registers: [0; 8],
+            segments: [0; 4],
+            flags: Flags::default(),
+            ip: 0,
+            memory: vec![0; MEMORY_SIZE],
+        }
+    }
+}
+
+impl Cpu {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn load(&mut self, address: usize, bytes: &[u8]) {
+        self.memory[address..address + bytes.len()].copy_from_slice(bytes);
+    }
+
+    pub fn register(&self, register: Register) -> u16 {
+        read_register(&self.registers, register)
+    }
+
+    pub fn set_register(&mut self, register: Register, value: u16) {
+        let slot = &mut self.registers[word_index(register)];
+        match register {
+            Register::AL | Register::CL | Register::DL | Register::BL => {
+                *slot = (*slot & 0xFF00) | (value & 0xFF);
+            }
+            Register::AH | Register::CH | Register::DH | Register::BH => {
+                *slot = (*slot & 0x00FF) | ((value & 0xFF) << 8);
+            }
+            _ => *slot = value,
+        }
+    }
+
+    pub fn segment(&self, segment: Segment) -> u16 {
+        self.segments[segment as usize]
+    }
+
+    pub fn set_segment(&mut self, segment: Segment, value: u16) {
+        self.segments[segment as usize] = value;
+    }
+
+    /// Physical address of the instruction at CS:IP.
+    pub fn instruction_address(&self) -> usize {
+        physical(self.segment(Segment::CS), self.ip)
+    }
+
+    /// The offset a memory operand refers to within its segment, or `None` for registers.
+    pub fn effective_address(&self, location: &Location) -> Option<u16> {
+        self.snapshot().effective_address(location)
+    }
+
+    /// Copies out everything but memory, for comparing against after a step.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            registers: self.registers,
+            segments: self.segments,
+            flags: self.flags,
+            ip: self.ip,
+        }
+    }
+
+    /// Physical address of a memory operand, using SS for `bp`-based forms and DS otherwise.
+    pub fn physical_address(&self, location: &Location) -> Option<usize> {
+        let segment = match location.register {
+            Some(Register::BP) => Segment::SS,
+            _ => Segment::DS,
+        };
+
+        self.effective_address(location)
+            .map(|offset| physical(self.segment(segment), offset))
+    }
+
+    pub fn read_memory(&self, address: usize, width: Width) -> u16 {
+        let lo = self.memory[address % MEMORY_SIZE] as u16;
+        match width {
+            Width::Byte => lo,
+            Width::Word => lo | (self.memory[(address + 1) % MEMORY_SIZE] as u16) << 8,
+        }
+    }
+
+    pub fn write_memory(&mut self, address: usize, width: Width, value: u16) {
+        self.memory[address % MEMORY_SIZE] = value as u8;
+        if width == Width::Word {
+            self.memory[(address + 1) % MEMORY_SIZE] = (value >> 8) as u8;
+        }
+    }
+
+    fn read(&self, location: &Location, width: Width) -> u16 {
+        match self.physical_address(location) {
+            Some(address) => self.read_memory(address, width),
+            None => self.register(location.register.expect("register operand")),
+        }
+    }
+
+    fn write(&mut self, location: &Location, width: Width, value: u16) {
+        match self.physical_address(location) {
+            Some(address) => self.write_memory(address, width, value),
+            None => self.set_register(location.register.expect("register operand"), value),
+        }
+    }
+
+    /// Decodes the instruction at CS:IP and executes it.
+    pub fn step(&mut self) -> Step {
+        let decoded = decode_at(&self.memory, self.instruction_address());
+        let taken = self.execute(&decoded);
+
+        Step { decoded, taken }
+    }
+
+    /// Executes `decoded` as though it had been fetched from CS:IP, returning whether
+    /// it transferred control elsewhere.
+    pub fn execute(&mut self, decoded: &Decoded) -> bool {
+        self.ip = self.ip.wrapping_add(decoded.size as u16);
+
+        let instruction = &decoded.instruction;
+
+        match instruction.operands() {
+            Operands::Locations { src, dest } => {
+                let width = dest.width().or(src.width()).unwrap_or(Width::Word);
+                let value = self.read(src, width);
+                self.apply(instruction, dest, width, value);
+                false
+            }
+            Operands::Immediate { data, dest } => {
+                let width = dest.width().unwrap_or(data.width());
+                let value = immediate_value(data) & mask(width);
+                self.apply(instruction, dest, width, value);
+                false
+            }
+            Operands::Unary { width, dest } => {
+                // inc and dec leave the carry as it was.
+                let carry = self.flags.carry;
+                let current = self.read(dest, width);
+                let result = match instruction {
+                    Instruction::Inc { .. } => self.add(current, 1, width),
+                    _ => self.sub(current, 1, width),
+                };
+                self.flags.carry = carry;
+                self.write(dest, width, result);
+                false
+            }
+            Operands::Stack { location } => {
+                match instruction {
+                    // The 8086 pushes SP as it is after the decrement.
+                    Instruction::Push { .. } if location.register == Some(Register::SP) => {
+                        self.push(self.register(Register::SP).wrapping_sub(2));
+                    }
+                    Instruction::Push { .. } => self.push(self.read(location, Width::Word)),
+                    _ => {
+                        let value = self.pop();
+                        self.write(location, Width::Word, value);
+                    }
+                }
+                false
+            }
+            Operands::Jump { increment } => {
+                if let Instruction::Call { .. } = instruction {
+                    self.push(self.ip);
+                }
+
+                let taken = self.branch(instruction);
+                if taken {
+                    self.ip = self.ip.wrapping_add(immediate_value(increment));
+                }
+                taken
+            }
+            Operands::None => match instruction {
+                Instruction::Ret => {
+                    self.ip = self.pop();
+                    true
+                }
+                _ => false,
+            },
+        }
+    }
+
+    fn push(&mut self, value: u16) {
+        let sp = self.register(Register::SP).wrapping_sub(2);
+        self.set_register(Register::SP, sp);
+        self.write_memory(physical(self.segment(Segment::SS), sp), Width::Word, value);
+    }
+
+    fn pop(&mut self) -> u16 {
+        let sp = self.register(Register::SP);
+        self.set_register(Register::SP, sp.wrapping_add(2));
+        self.read_memory(physical(self.segment(Segment::SS), sp), Width::Word)
+    }
+
+    fn apply(&mut self, instruction: &Instruction, dest: &Location, width: Width, value: u16) {
+        match instruction {
+            Instruction::Mov { .. } | Instruction::MovImmediate { .. } => {
+                self.write(dest, width, value);
+            }
+            Instruction::Add { .. } | Instruction::AddImmediate { .. } => {
+                let current = self.read(dest, width);
+                let result = self.add(current, value, width);
+                self.write(dest, width, result);
+            }
+            Instruction::Sub { .. } | Instruction::SubImmediate { .. } => {
+                let current = self.read(dest, width);
+                let result = self.sub(current, value, width);
+                self.write(dest, width, result);
+            }
+            Instruction::Cmp { .. } | Instruction::CmpImmediate { .. } => {
+                let current = self.read(dest, width);
+                self.sub(current, value, width);
+            }
+            Instruction::Xor { .. } | Instruction::XorImmediate { .. } => {
+                let result = self.read(dest, width) ^ value;
+                self.set_logic_flags(result, width);
+                self.write(dest, width, result);
+            }
+            Instruction::Test { .. } | Instruction::TestImmediate { .. } => {
+                let result = self.read(dest, width) & value;
+                self.set_logic_flags(result, width);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn add(&mut self, dest: u16, src: u16, width: Width) -> u16 {
+        let full = dest as u32 + src as u32;
+        let result = full as u16 & mask(width);
+
+        self.flags.carry = full > mask(width) as u32;
+        self.flags.auxiliary = (dest & 0xF) + (src & 0xF) > 0xF;
+        self.flags.overflow =
+            sign(dest, width) == sign(src, width) && sign(result, width) != sign(dest, width);
+        self.set_result_flags(result, width);
+
+        result
+    }
+
+    fn sub(&mut self, dest: u16, src: u16, width: Width) -> u16 {
+        let result = dest.wrapping_sub(src) & mask(width);
+
+        self.flags.carry = src > dest;
+        self.flags.auxiliary = (src & 0xF) > (dest & 0xF);
+        self.flags.overflow =
+            sign(dest, width) != sign(src, width) && sign(result, width) != sign(dest, width);
+        self.set_result_flags(result, width);
+
+        result
+    }
+
+    /// The 8086 leaves the auxiliary carry undefined here; this clears it.
+    fn set_logic_flags(&mut self, result: u16, width: Width) {
+        self.flags.carry = false;
+        self.flags.overflow = false;
+        self.flags.auxiliary = false;
+        self.set_result_flags(result, width);
+    }
+
+    fn set_result_flags(&mut self, result: u16, width: Width) {
+        self.flags.zero = result == 0;
+        self.flags.sign = sign(result, width);
+        self.flags.parity = (result as u8).count_ones().is_multiple_of(2);
+    }
+
+    fn branch(&mut self, instruction: &Instruction) -> bool {
+        let Flags {
+            carry,
+            parity,
+            zero,
+            sign,
+            overflow,
+            ..
+        } = self.flags;
+
+        match instruction {
+            Instruction::Je { .. } => zero,
+            Instruction::Jl { .. } => sign != overflow,
+            Instruction::Jle { .. } => zero || sign != overflow,
+            Instruction::Jb { .. } => carry,
+            Instruction::Jbe { .. } => carry || zero,
+            Instruction::Jp { .. } => parity,
+            Instruction::Jo { .. } => overflow,
+            Instruction::Js { .. } => sign,
+            Instruction::Jne { .. } => !zero,
+            Instruction::Jnl { .. } => sign == overflow,
+            Instruction::Jnle { .. } => !zero && sign == overflow,
+            Instruction::Jnb { .. } => !carry,
+            Instruction::Jnbe { .. } => !carry && !zero,
+            Instruction::Jnp { .. } => !parity,
+            Instruction::Jno { .. } => !overflow,
+            Instruction::Jns { .. } => !sign,
+            Instruction::Loop { .. } => self.decrement_cx() != 0,
+            Instruction::Loopz { .. } => self.decrement_cx() != 0 && zero,
+            Instruction::Loopnz { .. } => self.decrement_cx() != 0 && !zero,
+            Instruction::Jcxz { .. } => self.register(Register::CX) == 0,
+            Instruction::Jmp { .. } | Instruction::Call { .. } => true,
+            _ => unreachable!(),
+        }
+    }
+
+    fn decrement_cx(&mut self) -> u16 {
+        let cx = self.register(Register::CX).wrapping_sub(1);
+        self.set_register(Register::CX, cx);
+        cx
+    }
+}
+
+/// The registers, segments, flags and IP of a [`Cpu`] without its memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Snapshot {
+    registers: [u16; 8],
+    segments: [u16; 4],
+    pub flags: Flags,
+    pub ip: u16,
+}
+
+impl Snapshot {
+    pub fn register(&self, register: Register) -> u16 {
+        read_register(&self.registers, register)
+    }
+
+    pub fn segment(&self, segment: Segment) -> u16 {
+        self.segments[segment as usize]
+    }
+
+    /// The offset a memory operand refers to within its segment, or `None` for registers.
+    pub fn effective_address(&self, location: &Location) -> Option<u16> {
+        if !location.is_mem_addr {
+            return None;
+        }
+
+        let base = location
+            .register
+            .map_or(0, |register| self.register(register));
+        let index = location
+            .addr_calc
+            .map_or(0, |register| self.register(register));
+        let displacement = location.displacement.unwrap_or(0) as u16;
+
+        Some(base.wrapping_add(index).wrapping_add(displacement))
+    }
+}
+
+/// Lists the registers, flags and IP that differ between `before` and `after`,
+/// in the `ax:0x0->0x1` form the course listings use.
+pub struct Changes {
+    pub before: Snapshot,
+    pub after: Snapshot,
+}
+
+impl Display for Changes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Changes { before, after } = self;
+
+        for register in WORD_REGISTERS {
+            let (old, new) = (before.register(register), after.register(register));
+            if old != new {
+                write!(f, "{register}:{old:#x}->{new:#x} ")?;
+            }
+        }
+        for segment in SEGMENTS {
+            let (old, new) = (before.segment(segment), after.segment(segment));
+            if old != new {
+                write!(f, "{segment}:{old:#x}->{new:#x} ")?;
+            }
+        }
+
+        write!(f, "ip:{:#x}->{:#x}", before.ip, after.ip)?;
+
+        if before.flags != after.flags {
+            write!(f, " flags:{}->{}", before.flags, after.flags)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Cpu {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Final registers:")?;
+        for register in WORD_REGISTERS {
+            let value = self.register(register);
+            if value != 0 {
+                writeln!(f, "      {register}: {value:#06x} ({value})")?;
+            }
+        }
+        for segment in SEGMENTS {
+            let value = self.segment(segment);
+            if value != 0 {
+                writeln!(f, "      {segment}: {value:#06x} ({value})")?;
+            }
+        }
+        writeln!(f, "      ip: {:#06x} ({})", self.ip, self.ip)?;
+        write!(f, "   flags: {}", self.flags)
+    }
+}
+
+pub fn physical(segment: u16, offset: u16) -> usize {
+    (((segment as usize) << 4) + offset as usize) % MEMORY_SIZE
+}
+
+fn read_register(registers: &[u16; 8], register: Register) -> u16 {
+    let value = registers[word_index(register)];
+    match register {
+        Register::AL | Register::CL | Register::DL | Register::BL => value & 0xFF,
+        Register::AH | Register::CH | Register::DH | Register::BH => value >> 8,
+        _ => value,
+    }
+}
+
+fn word_index(register: Register) -> usize {
+    match register {
+        Register::AX | Register::AL | Register::AH => 0,
+        Register::CX | Register::CL | Register::CH => 1,
+        Register::DX | Register::DL | Register::DH => 2,
+        Register::BX | Register::BL | Register::BH => 3,
+        Register::SP => 4,
+        Register::BP => 5,
+        Register::SI => 6,
+        Register::DI => 7,
+    }
+}
+
+pub(crate) fn immediate_value(data: &Immediate) -> u16 {
+    match data {
+        Immediate::Byte(data) => *data as i16 as u16,
+        Immediate::Word(data) => *data as u16,
+    }
+}
+
+pub(crate) fn mask(width: Width) -> u16 {
+    match width {
+        Width::Byte => 0xFF,
+        Width::Word => 0xFFFF,
+    }
+}
+
+fn sign(value: u16, width: Width) -> bool {
+    match width {
+        Width::Byte => value & 0x80 != 0,
+        Width::Word => value & 0x8000 != 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(bytes: &[u8]) -> Cpu {
+        let mut cpu = Cpu::new();
+        cpu.load(0, bytes);
+        while (cpu.ip as usize) < bytes.len() {
+            cpu.step();
+        }
+        cpu
+    }
+
+    #[test]
+    fn xor_clears_the_carry_and_overflow() {
+        // mov ax, 0x7FFF; add ax, 0x7FFF; xor ax, ax
+        let cpu = run(&[0xB8, 0xFF, 0x7F, 0x05, 0xFF, 0x7F, 0x31, 0xC0]);
+
+        assert_eq!(cpu.register(Register::AX), 0);
+        assert_eq!(cpu.flags.to_string(), "PZ");
+    }
+
+    #[test]
+    fn test_sets_flags_without_writing() {
+        // mov al, 0x81; test al, 0x80
+        let cpu = run(&[0xB0, 0x81, 0xA8, 0x80]);
+
+        assert_eq!(cpu.register(Register::AL), 0x81);
+        assert_eq!(cpu.flags.to_string(), "S");
+    }
+
+    #[test]
+    fn inc_and_dec_leave_the_carry_alone() {
+        // mov ax, 0xFFFF; add ax, 1; dec ax; inc ax
+        let cpu = run(&[0xB8, 0xFF, 0xFF, 0x05, 0x01, 0x00, 0x48, 0x40]);
+
+        assert_eq!(cpu.register(Register::AX), 0);
+        assert!(cpu.flags.carry);
+        assert!(cpu.flags.zero);
+    }
+
+    #[test]
+    fn add_sets_overflow_and_auxiliary_carry() {
+        // mov ax, 0x7FFF; add ax, 1
+        let cpu = run(&[0xB8, 0xFF, 0x7F, 0x05, 0x01, 0x00]);
+
+        assert_eq!(cpu.register(Register::AX), 0x8000);
+        assert_eq!(cpu.flags.to_string(), "PASO");
+    }
+
+    #[test]
+    fn sub_borrows_below_zero() {
+        // mov bx, 1; sub bx, 2
+        let cpu = run(&[0xBB, 0x01, 0x00, 0x83, 0xEB, 0x02]);
+
+        assert_eq!(cpu.register(Register::BX), 0xFFFF);
+        assert_eq!(cpu.flags.to_string(), "CPAS");
+    }
+
+    #[test]
+    fn cmp_sets_flags_and_mov_keeps_them() {
+        // mov cx, 5; cmp cx, 5; mov ax, 1
+        let cpu = run(&[0xB9, 0x05, 0x00, 0x83, 0xF9, 0x05, 0xB8, 0x01, 0x00]);
+
+        assert_eq!(cpu.register(Register::CX), 5);
+        assert_eq!(cpu.register(Register::AX), 1);
+        assert_eq!(cpu.flags.to_string(), "PZ");
+    }
+
+    #[test]
+    fn conditional_jumps_follow_the_flags() {
+        // mov ax, 1; cmp ax, 2; jl 0xb; mov bx, 1; jnb 0x10; mov dx, 1
+        let bytes = [
+            0xB8, 0x01, 0x00, 0x83, 0xF8, 0x02, 0x7C, 0x03, 0xBB, 0x01, 0x00, 0x73, 0x03, 0xBA,
+            0x01, 0x00,
+        ];
+        let mut cpu = Cpu::new();
+        cpu.load(0, &bytes);
+
+        let taken: Vec<bool> = (0..5).map(|_| cpu.step().taken).collect();
+
+        assert_eq!(taken, [false, false, true, false, false]);
+        assert_eq!(cpu.register(Register::BX), 0);
+        assert_eq!(cpu.register(Register::DX), 1);
+    }
+
+    #[test]
+    fn loop_counts_cx_down_to_zero() {
+        // mov cx, 3; inc ax; loop 3
+        let cpu = run(&[0xB9, 0x03, 0x00, 0x40, 0xE2, 0xFD]);
+
+        assert_eq!(cpu.register(Register::AX), 3);
+        assert_eq!(cpu.register(Register::CX), 0);
+    }
+
+    #[test]
+    fn loopz_and_loopnz_also_stop_on_the_zero_flag() {
+        // mov cx, 5; inc ax; cmp ax, 1; loopz 3
+        let cpu = run(&[0xB9, 0x05, 0x00, 0x40, 0x83, 0xF8, 0x01, 0xE1, 0xFA]);
+        assert_eq!(cpu.register(Register::AX), 2);
+        assert_eq!(cpu.register(Register::CX), 3);
+
+        // mov cx, 5; inc ax; cmp ax, 2; loopnz 3
+        let cpu = run(&[0xB9, 0x05, 0x00, 0x40, 0x83, 0xF8, 0x02, 0xE0, 0xFA]);
+        assert_eq!(cpu.register(Register::AX), 2);
+        assert_eq!(cpu.register(Register::CX), 3);
+    }
+
+    #[test]
+    fn call_pushes_the_return_address_for_ret() {
+        // mov sp, 0x100; call 0xb; mov bx, 1; jmp 0xf; mov ax, 2; ret
+        let cpu = run(&[
+            0xBC, 0x00, 0x01, 0xE8, 0x05, 0x00, 0xBB, 0x01, 0x00, 0xEB, 0x04, 0xB8, 0x02, 0x00,
+            0xC3,
+        ]);
+
+        assert_eq!(cpu.register(Register::AX), 2);
+        assert_eq!(cpu.register(Register::BX), 1);
+        assert_eq!(cpu.register(Register::SP), 0x100);
+        assert_eq!(cpu.read_memory(0xFE, Width::Word), 6);
+    }
+
+    #[test]
+    fn snapshots_report_only_what_changed() {
+        let mut cpu = Cpu::new();
+        // mov ax, 1
+        cpu.load(0, &[0xB8, 0x01, 0x00]);
+
+        let before = cpu.snapshot();
+        cpu.step();
+        let changes = Changes {
+            before,
+            after: cpu.snapshot(),
+        };
+
+        assert_eq!(changes.to_string(), "ax:0x0->0x1 ip:0x0->0x3");
+    }
+}
@@ -0,0 +1,841 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display},
+};
+
+use crate::{
+    cfg::Cfg,
+    format::FormatOptions,
+    instruction::{Instruction, Location, Operands, Register, Width},
+    sim::{immediate_value, mask},
+    Decoded,
+};
+
+const WORD_REGISTERS: [Register; 8] = [
+    Register::AX,
+    Register::CX,
+    Register::DX,
+    Register::BX,
+    Register::SP,
+    Register::BP,
+    Register::SI,
+    Register::DI,
+];
+
+/// An unknown the evaluator can't break down any further.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Atom {
+    /// A register's value before the code ran, printed as `bx0`.
+    Register(Register),
+    /// Memory as it was before the code ran, printed as `[bx0 + 4]`, or after
+    /// `generation` stores when one of them might overlap it, printed `[bx0 + 4]@2`.
+    Load {
+        address: Expr,
+        width: Width,
+        generation: usize,
+    },
+    /// A byte of a word that isn't linear in anything smaller.
+    Low(Expr),
+    High(Expr),
+    /// A word put together from separately written halves.
+    Join {
+        high: Expr,
+        low: Expr,
+    },
+    /// Bitwise operations, with their operands in order so that `a ^ b` and
+    /// `b ^ a` are the same atom.
+    Xor(Expr, Expr),
+    And(Expr, Expr),
+}
+
+impl Display for Atom {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Atom::Register(register) => write!(f, "{register}0"),
+            Atom::Load {
+                address,
+                width,
+                generation,
+            } => {
+                if *width == Width::Byte {
+                    write!(f, "byte ")?;
+                }
+                write!(f, "[{address}]")?;
+                if *generation > 0 {
+                    write!(f, "@{generation}")?;
+                }
+                Ok(())
+            }
+            Atom::Low(expr) => write!(f, "lo({expr})"),
+            Atom::High(expr) => write!(f, "hi({expr})"),
+            Atom::Join { high, low } => write!(f, "({high}:{low})"),
+            Atom::Xor(a, b) => write!(f, "({a} ^ {b})"),
+            Atom::And(a, b) => write!(f, "({a} & {b})"),
+        }
+    }
+}
+
+/// A sum of atoms times coefficients plus a constant, modulo 2^8 or 2^16. Only
+/// the sum is normalized: atoms are compared as written, so matching
+/// expressions always have the same value, but `(ax0 ^ bx0) ^ bx0` and `ax0`
+/// don't match even though they do.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Expr {
+    width: Width,
+    constant: u16,
+    terms: BTreeMap<Atom, u16>,
+}
+
+impl Expr {
+    pub fn constant(value: u16, width: Width) -> Self {
+        Self {
+            width,
+            constant: value & mask(width),
+            terms: BTreeMap::new(),
+        }
+    }
+
+    pub fn atom(atom: Atom, width: Width) -> Self {
+        Self {
+            width,
+            constant: 0,
+            terms: BTreeMap::from([(atom, 1)]),
+        }
+    }
+
+    pub fn width(&self) -> Width {
+        self.width
+    }
+
+    pub fn as_constant(&self) -> Option<u16> {
+        self.terms.is_empty().then_some(self.constant)
+    }
+
+    /// The single atom this is, with coefficient one and nothing added.
+    fn as_atom(&self) -> Option<&Atom> {
+        match (self.constant, self.terms.len()) {
+            (0, 1) => self
+                .terms
+                .iter()
+                .next()
+                .filter(|(_, coefficient)| **coefficient == 1)
+                .map(|(atom, _)| atom),
+            _ => None,
+        }
+    }
+
+    pub fn add(&self, other: &Expr) -> Expr {
+        self.combine(other, 1)
+    }
+
+    pub fn sub(&self, other: &Expr) -> Expr {
+        self.combine(other, mask(self.width))
+    }
+
+    fn combine(&self, other: &Expr, sign: u16) -> Expr {
+        let width = self.width;
+        let mut result = self.clone();
+
+        result.constant = self
+            .constant
+            .wrapping_add(other.constant.wrapping_mul(sign))
+            & mask(width);
+        for (atom, coefficient) in &other.terms {
+            let entry = result.terms.entry(atom.clone()).or_insert(0);
+            *entry = entry.wrapping_add(coefficient.wrapping_mul(sign)) & mask(width);
+        }
+        result.terms.retain(|_, coefficient| *coefficient != 0);
+
+        result
+    }
+
+    /// Reads the expression at `width`, which for a byte keeps the low eight bits.
+    fn truncate(&self, width: Width) -> Expr {
+        let mut result = self.clone();
+        result.width = width;
+        result.constant &= mask(width);
+        for coefficient in result.terms.values_mut() {
+            *coefficient &= mask(width);
+        }
+        result.terms.retain(|_, coefficient| *coefficient != 0);
+        result
+    }
+
+    fn signed(&self, value: u16) -> i32 {
+        match self.width {
+            Width::Byte => value as u8 as i8 as i32,
+            Width::Word => value as i16 as i32,
+        }
+    }
+}
+
+impl Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut first = true;
+
+        for (atom, coefficient) in &self.terms {
+            let coefficient = self.signed(*coefficient);
+            let sign = match (first, coefficient < 0) {
+                (true, false) => "",
+                (true, true) => "-",
+                (false, false) => " + ",
+                (false, true) => " - ",
+            };
+            match coefficient.abs() {
+                1 => write!(f, "{sign}{atom}")?,
+                scale => write!(f, "{sign}{scale}*{atom}")?,
+            }
+            first = false;
+        }
+
+        let constant = self.signed(self.constant);
+        match (first, constant) {
+            (true, constant) => write!(f, "{constant}"),
+            (false, 0) => Ok(()),
+            (false, constant) if constant < 0 => write!(f, " - {}", -constant),
+            (false, constant) => write!(f, " + {constant}"),
+        }
+    }
+}
+
+/// What the flags were last computed from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FlagSource {
+    /// `lhs + rhs` or `lhs - rhs`, as add, sub and cmp leave them.
+    Arithmetic {
+        subtract: bool,
+        lhs: Expr,
+        rhs: Expr,
+    },
+    /// `inc` or `dec` of `value`, which set every flag but the carry. That is
+    /// still whatever `carry` left it as, or what it was before the code ran.
+    Step {
+        decrement: bool,
+        value: Expr,
+        carry: Option<Box<FlagSource>>,
+    },
+    /// The result of xor or test, which also clear the carry and overflow.
+    Logical(Expr),
+}
+
+impl Display for FlagSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let byte = |expr: &Expr| {
+            if expr.width() == Width::Byte {
+                "byte "
+            } else {
+                ""
+            }
+        };
+
+        match self {
+            FlagSource::Arithmetic { subtract, lhs, rhs } => {
+                let op = if *subtract { "-" } else { "+" };
+                write!(f, "{}{lhs} {op} {rhs}", byte(lhs))
+            }
+            FlagSource::Step {
+                decrement,
+                value,
+                carry,
+            } => {
+                let op = if *decrement { "dec" } else { "inc" };
+                write!(f, "{op} {}{value}", byte(value))?;
+                match carry {
+                    Some(carry) => write!(f, ", carry of {carry}"),
+                    None => write!(f, ", carry unchanged"),
+                }
+            }
+            FlagSource::Logical(result) => write!(f, "logic {}{result}", byte(result)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Store {
+    pub address: Expr,
+    pub width: Width,
+    pub value: Expr,
+}
+
+/// An instruction the evaluator has no model for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unsupported {
+    pub offset: usize,
+    pub mnemonic: &'static str,
+}
+
+impl Display for Unsupported {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "can't evaluate {} at {:04X} symbolically",
+            self.mnemonic, self.offset
+        )
+    }
+}
+
+/// Registers, memory and flags as expressions of the state before the code ran.
+/// Memory is one flat space, so DS and SS are assumed to be the same segment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct State {
+    registers: [Expr; 8],
+    /// Every store in order; a later one to the same address supersedes an earlier one.
+    pub stores: Vec<Store>,
+    pub flags: Option<FlagSource>,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            registers: WORD_REGISTERS
+                .map(|register| Expr::atom(Atom::Register(register), Width::Word)),
+            stores: vec![],
+            flags: None,
+        }
+    }
+}
+
+impl State {
+    /// Nothing run yet: every register holds its own initial value.
+    pub fn initial() -> Self {
+        Self::default()
+    }
+
+    /// Evaluates `lines` in order, ignoring where any jump would go.
+    pub fn run(lines: &[Decoded]) -> Result<Self, Unsupported> {
+        let mut state = Self::initial();
+        for decoded in lines {
+            state.execute(decoded)?;
+        }
+        Ok(state)
+    }
+
+    pub fn register(&self, register: Register) -> Expr {
+        let word = &self.registers[word_index(register)];
+        match register {
+            Register::AL | Register::CL | Register::DL | Register::BL => low(word),
+            Register::AH | Register::CH | Register::DH | Register::BH => high(word),
+            _ => word.clone(),
+        }
+    }
+
+    pub fn set_register(&mut self, register: Register, value: Expr) {
+        let index = word_index(register);
+        let word = &self.registers[index];
+
+        self.registers[index] = match register {
+            Register::AL | Register::CL | Register::DL | Register::BL => join(high(word), value),
+            Register::AH | Register::CH | Register::DH | Register::BH => join(value, low(word)),
+            _ => value,
+        };
+    }
+
+    /// The final value of each store that a later store to the same address
+    /// doesn't replace, in the order they were made.
+    pub fn memory(&self) -> Vec<&Store> {
+        self.stores
+            .iter()
+            .enumerate()
+            .filter(|(index, store)| {
+                !self.stores[index + 1..]
+                    .iter()
+                    .any(|later| later.address == store.address && later.width == store.width)
+            })
+            .map(|(_, store)| store)
+            .collect()
+    }
+
+    pub fn execute(&mut self, decoded: &Decoded) -> Result<(), Unsupported> {
+        let instruction = &decoded.instruction;
+
+        match instruction.operands() {
+            Operands::Locations { src, dest } => {
+                let width = dest.width().or(src.width()).unwrap_or(Width::Word);
+                let value = self.read(src, width);
+                self.apply(instruction, dest, width, value);
+            }
+            Operands::Immediate { data, dest } => {
+                let width = dest.width().unwrap_or(data.width());
+                let value = Expr::constant(immediate_value(data), width);
+                self.apply(instruction, dest, width, value);
+            }
+            Operands::Unary { width, dest } => {
+                let decrement = matches!(instruction, Instruction::Dec { .. });
+                let current = self.read(dest, width);
+                let one = Expr::constant(1, width);
+                let result = match decrement {
+                    true => current.sub(&one),
+                    false => current.add(&one),
+                };
+                let carry = match self.flags.take() {
+                    Some(FlagSource::Step { carry, .. }) => carry,
+                    flags => flags.map(Box::new),
+                };
+                self.flags = Some(FlagSource::Step {
+                    decrement,
+                    value: current,
+                    carry,
+                });
+                self.write(dest, width, result);
+            }
+            Operands::Stack { location } => match instruction {
+                Instruction::Push { .. } => {
+                    let value = self.read(location, Width::Word);
+                    let sp = self.push();
+                    // The 8086 pushes SP as it is after the decrement.
+                    let value = match location.register {
+                        Some(Register::SP) if !location.is_mem_addr => sp.clone(),
+                        _ => value,
+                    };
+                    self.store(sp, Width::Word, value);
+                }
+                _ => {
+                    let sp = self.register(Register::SP);
+                    let value = self.load(sp.clone(), Width::Word);
+                    let two = Expr::constant(2, Width::Word);
+                    self.set_register(Register::SP, sp.add(&two));
+                    self.write(location, Width::Word, value);
+                }
+            },
+            Operands::Jump { .. } => match instruction {
+                Instruction::Loop { .. }
+                | Instruction::Loopz { .. }
+                | Instruction::Loopnz { .. } => {
+                    let one = Expr::constant(1, Width::Word);
+                    let cx = self.register(Register::CX).sub(&one);
+                    self.set_register(Register::CX, cx);
+                }
+                Instruction::Call { .. } => {
+                    let sp = self.push();
+                    let return_address =
+                        Expr::constant((decoded.offset + decoded.size) as u16, Width::Word);
+                    self.store(sp, Width::Word, return_address);
+                }
+                _ => {}
+            },
+            Operands::None => match instruction {
+                Instruction::Ret => {
+                    let two = Expr::constant(2, Width::Word);
+                    let sp = self.register(Register::SP).add(&two);
+                    self.set_register(Register::SP, sp);
+                }
+                _ => {
+                    return Err(Unsupported {
+                        offset: decoded.offset,
+                        mnemonic: instruction.mnemonic(),
+                    })
+                }
+            },
+        }
+
+        Ok(())
+    }
+
+    fn apply(&mut self, instruction: &Instruction, dest: &Location, width: Width, value: Expr) {
+        match instruction {
+            Instruction::Mov { .. } | Instruction::MovImmediate { .. } => {
+                self.write(dest, width, value);
+            }
+            Instruction::Add { .. } | Instruction::AddImmediate { .. } => {
+                let current = self.read(dest, width);
+                let result = current.add(&value);
+                self.flags = Some(FlagSource::Arithmetic {
+                    subtract: false,
+                    lhs: current,
+                    rhs: value,
+                });
+                self.write(dest, width, result);
+            }
+            Instruction::Sub { .. }
+            | Instruction::SubImmediate { .. }
+            | Instruction::Cmp { .. }
+            | Instruction::CmpImmediate { .. } => {
+                let current = self.read(dest, width);
+                let result = current.sub(&value);
+                self.flags = Some(FlagSource::Arithmetic {
+                    subtract: true,
+                    lhs: current,
+                    rhs: value,
+                });
+                if matches!(
+                    instruction,
+                    Instruction::Sub { .. } | Instruction::SubImmediate { .. }
+                ) {
+                    self.write(dest, width, result);
+                }
+            }
+            Instruction::Xor { .. } | Instruction::XorImmediate { .. } => {
+                let result = xor(&self.read(dest, width), &value);
+                self.flags = Some(FlagSource::Logical(result.clone()));
+                self.write(dest, width, result);
+            }
+            Instruction::Test { .. } | Instruction::TestImmediate { .. } => {
+                let result = and(&self.read(dest, width), &value);
+                self.flags = Some(FlagSource::Logical(result));
+            }
+            _ => unreachable!("only mov, add, sub, cmp, xor and test take data operands"),
+        }
+    }
+
+    fn address(&self, location: &Location) -> Expr {
+        let mut address = Expr::constant(location.displacement.unwrap_or(0) as u16, Width::Word);
+        for register in [location.register, location.addr_calc]
+            .into_iter()
+            .flatten()
+        {
+            address = address.add(&self.register(register));
+        }
+        address
+    }
+
+    /// What `location` holds, read at `width`.
+    pub fn read(&self, location: &Location, width: Width) -> Expr {
+        match location.register {
+            Some(register) if !location.is_mem_addr => self.register(register),
+            _ => self.load(self.address(location), width),
+        }
+    }
+
+    fn write(&mut self, location: &Location, width: Width, value: Expr) {
+        match location.register {
+            Some(register) if !location.is_mem_addr => self.set_register(register, value),
+            _ => {
+                let address = self.address(location);
+                self.store(address, width, value);
+            }
+        }
+    }
+
+    fn push(&mut self) -> Expr {
+        let two = Expr::constant(2, Width::Word);
+        let sp = self.register(Register::SP).sub(&two);
+        self.set_register(Register::SP, sp.clone());
+        sp
+    }
+
+    fn store(&mut self, address: Expr, width: Width, value: Expr) {
+        self.stores.push(Store {
+            address,
+            width,
+            value: value.truncate(width),
+        });
+    }
+
+    /// Finds what the latest store to `address` left there, or the right half of
+    /// it for a byte read from a stored word. A store that might partly overlap
+    /// it, or whose address differs by something other than a constant, leaves
+    /// the result opaque.
+    fn load(&self, address: Expr, width: Width) -> Expr {
+        let size = |width: Width| match width {
+            Width::Byte => 1,
+            Width::Word => 2,
+        };
+
+        for (index, store) in self.stores.iter().enumerate().rev() {
+            let distance = address
+                .sub(&store.address)
+                .as_constant()
+                .map(|d| d as i16 as i32);
+
+            match distance {
+                Some(0) if store.width == width => return store.value.clone(),
+                Some(0) if width == Width::Byte && store.width == Width::Word => {
+                    return low(&store.value)
+                }
+                Some(1) if width == Width::Byte && store.width == Width::Word => {
+                    return high(&store.value)
+                }
+                Some(distance) if distance >= size(store.width) || distance + size(width) <= 0 => {}
+                _ => {
+                    let load = Atom::Load {
+                        address,
+                        width,
+                        generation: index + 1,
+                    };
+                    return Expr::atom(load, width);
+                }
+            }
+        }
+
+        let load = Atom::Load {
+            address,
+            width,
+            generation: 0,
+        };
+        Expr::atom(load, width)
+    }
+}
+
+impl Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let initial = State::initial();
+        let mut lines = vec![];
+
+        for register in WORD_REGISTERS {
+            let value = self.register(register);
+            if value != initial.register(register) {
+                lines.push(format!("{register} = {value}"));
+            }
+        }
+        for store in self.memory() {
+            let size = match store.width {
+                Width::Byte => "byte ",
+                Width::Word => "",
+            };
+            lines.push(format!("{size}[{}] = {}", store.address, store.value));
+        }
+        if let Some(flags) = &self.flags {
+            lines.push(format!("flags of {flags}"));
+        }
+
+        if lines.is_empty() {
+            return write!(f, "no effect");
+        }
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+fn low(word: &Expr) -> Expr {
+    if let Some(value) = word.as_constant() {
+        return Expr::constant(value, Width::Byte);
+    }
+
+    let split = match word.as_atom() {
+        Some(Atom::Register(register)) => {
+            halves(*register).map(|(low, _)| Expr::atom(Atom::Register(low), Width::Byte))
+        }
+        Some(Atom::Join { low, .. }) => Some(low.clone()),
+        _ => None,
+    };
+    split.unwrap_or_else(|| Expr::atom(Atom::Low(word.clone()), Width::Byte))
+}
+
+fn high(word: &Expr) -> Expr {
+    if let Some(value) = word.as_constant() {
+        return Expr::constant(value >> 8, Width::Byte);
+    }
+
+    let split = match word.as_atom() {
+        Some(Atom::Register(register)) => {
+            halves(*register).map(|(_, high)| Expr::atom(Atom::Register(high), Width::Byte))
+        }
+        Some(Atom::Join { high, .. }) => Some(high.clone()),
+        _ => None,
+    };
+    split.unwrap_or_else(|| Expr::atom(Atom::High(word.clone()), Width::Byte))
+}
+
+/// Puts a word back together from its halves, undoing a split where it can.
+fn join(high: Expr, low: Expr) -> Expr {
+    if let (Some(high), Some(low)) = (high.as_constant(), low.as_constant()) {
+        return Expr::constant(high << 8 | low, Width::Word);
+    }
+
+    match (high.as_atom(), low.as_atom()) {
+        (Some(Atom::Register(high)), Some(Atom::Register(low)))
+            if halves(high.full()) == Some((*low, *high)) =>
+        {
+            return Expr::atom(Atom::Register(high.full()), Width::Word);
+        }
+        (Some(Atom::High(high)), Some(Atom::Low(low))) if high == low => return high.clone(),
+        _ => {}
+    }
+
+    Expr::atom(Atom::Join { high, low }, Width::Word)
+}
+
+fn xor(a: &Expr, b: &Expr) -> Expr {
+    let width = a.width();
+    match (a.as_constant(), b.as_constant()) {
+        (Some(x), Some(y)) => Expr::constant(x ^ y, width),
+        _ if a == b => Expr::constant(0, width),
+        (Some(0), _) => b.clone(),
+        (_, Some(0)) => a.clone(),
+        _ => {
+            let (a, b) = ordered(a, b);
+            Expr::atom(Atom::Xor(a, b), width)
+        }
+    }
+}
+
+fn and(a: &Expr, b: &Expr) -> Expr {
+    let width = a.width();
+    match (a.as_constant(), b.as_constant()) {
+        (Some(x), Some(y)) => Expr::constant(x & y, width),
+        (Some(0), _) | (_, Some(0)) => Expr::constant(0, width),
+        _ if a == b => a.clone(),
+        _ => {
+            let (a, b) = ordered(a, b);
+            Expr::atom(Atom::And(a, b), width)
+        }
+    }
+}
+
+fn ordered(a: &Expr, b: &Expr) -> (Expr, Expr) {
+    match a <= b {
+        true => (a.clone(), b.clone()),
+        false => (b.clone(), a.clone()),
+    }
+}
+
+/// The named low and high bytes of AX-DX. The other word registers have none,
+/// so their bytes stay `lo(..)` and `hi(..)` projections.
+fn halves(register: Register) -> Option<(Register, Register)> {
+    match register {
+        Register::AX => Some((Register::AL, Register::AH)),
+        Register::CX => Some((Register::CL, Register::CH)),
+        Register::DX => Some((Register::DL, Register::DH)),
+        Register::BX => Some((Register::BL, Register::BH)),
+        _ => None,
+    }
+}
+
+fn word_index(register: Register) -> usize {
+    WORD_REGISTERS
+        .iter()
+        .position(|word| *word == register.full())
+        .expect("every register is part of a word register")
+}
+
+/// Each basic block's instructions followed by what it computes.
+pub struct Summaries<'a> {
+    pub cfg: &'a Cfg,
+    pub options: FormatOptions,
+}
+
+impl<'a> Summaries<'a> {
+    pub fn new(cfg: &'a Cfg, options: FormatOptions) -> Self {
+        Self { cfg, options }
+    }
+}
+
+impl Display for Summaries<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cfg = self.cfg;
+
+        for (index, block) in cfg.blocks.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+
+            writeln!(f, "block at {:04X}:", block.start)?;
+            for decoded in cfg.instructions(index) {
+                let instruction = decoded.instruction.formatted(&self.options);
+                writeln!(f, "    {:04X}: {instruction}", decoded.offset)?;
+            }
+
+            let lines = &cfg.lines[block.lines.clone()];
+            let summary = match State::run(lines) {
+                Ok(state) => state.to_string(),
+                Err(err) => err.to_string(),
+            };
+            for line in summary.lines() {
+                writeln!(f, "  {line}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode_with_offsets;
+
+    fn run(bytes: &[u8]) -> State {
+        State::run(&decode_with_offsets(bytes)).unwrap()
+    }
+
+    fn initial(register: Register) -> Expr {
+        Expr::atom(Atom::Register(register), register.width())
+    }
+
+    #[test]
+    fn arithmetic_collects_like_terms() {
+        // mov ax, bx; add ax, bx; sub ax, 4
+        let state = run(&[0x89, 0xD8, 0x01, 0xD8, 0x83, 0xE8, 0x04]);
+
+        assert_eq!(state.register(Register::AX).to_string(), "2*bx0 - 4");
+        assert_eq!(
+            state.flags.map(|flags| flags.to_string()),
+            Some("2*bx0 - 4".to_string())
+        );
+    }
+
+    #[test]
+    fn a_byte_store_says_nothing_about_the_next_byte() {
+        // mov byte [bx], 7; mov al, [bx + 1]
+        let state = run(&[0xC6, 0x07, 0x07, 0x8A, 0x47, 0x01]);
+
+        assert_eq!(state.register(Register::AL).to_string(), "byte [bx0 + 1]");
+    }
+
+    #[test]
+    fn loads_see_earlier_stores_to_the_same_address() {
+        // mov [si], ax; mov bx, [si]
+        let state = run(&[0x89, 0x04, 0x8B, 0x1C]);
+
+        assert_eq!(state.register(Register::BX), initial(Register::AX));
+        assert_eq!(state.memory().len(), 1);
+    }
+
+    #[test]
+    fn loads_that_might_overlap_a_store_are_opaque() {
+        // mov [si], ax; mov bx, [di]
+        let state = run(&[0x89, 0x04, 0x8B, 0x1D]);
+
+        assert_eq!(state.register(Register::BX).to_string(), "[di0]@1");
+    }
+
+    #[test]
+    fn halves_written_separately_join_back_up() {
+        // mov al, bl; mov ah, bh
+        let state = run(&[0x88, 0xD8, 0x88, 0xFC]);
+
+        assert_eq!(state.register(Register::AX), initial(Register::BX));
+    }
+
+    #[test]
+    fn push_then_pop_moves_through_the_stack() {
+        // push ax; pop bx
+        let state = run(&[0x50, 0x5B]);
+
+        assert_eq!(state.register(Register::BX), initial(Register::AX));
+        assert_eq!(state.register(Register::SP), initial(Register::SP));
+    }
+
+    #[test]
+    fn compare_only_sets_flags() {
+        // cmp ax, 5
+        let state = run(&[0x83, 0xF8, 0x05]);
+
+        assert_eq!(state.register(Register::AX), initial(Register::AX));
+        assert!(state.stores.is_empty());
+        assert_eq!(state.flags.unwrap().to_string(), "ax0 - 5");
+    }
+
+    #[test]
+    fn unknown_instructions_are_unsupported() {
+        assert_eq!(
+            State::run(&decode_with_offsets(&[0x50, 0x0F])),
+            Err(Unsupported {
+                offset: 1,
+                mnemonic: "noop"
+            })
+        );
+    }
+
+    #[test]
+    fn byte_of_a_stored_word_without_named_halves() {
+        // mov [bx], sp; mov al, [bx]
+        let state = run(&[0x89, 0x27, 0x8A, 0x07]);
+
+        assert_eq!(state.register(Register::AL).to_string(), "lo(sp0)");
+
+        // mov [bx], si; mov al, [bx + 1]
+        let state = run(&[0x89, 0x37, 0x8A, 0x47, 0x01]);
+
+        assert_eq!(state.register(Register::AL).to_string(), "hi(si0)");
+    }
+}
@@ -0,0 +1,132 @@
+use std::{collections::BTreeMap, ops::Range};
+
+use crate::{decode_at, instruction::Instruction, Decoded};
+
+/// What recursive descent found: the instructions reached, in address order,
+/// and the parts of the code ranges nothing reached.
+pub struct Traversal {
+    pub lines: Vec<Decoded>,
+    pub data: Vec<Range<usize>>,
+}
+
+/// Disassembles `bytes` by following control flow from `entries` rather than
+/// sweeping straight through, so tables and strings behind a `jmp` or `ret`
+/// don't come out as instructions. Jumps, loops and calls are followed to their
+/// targets, and everything but `jmp` and `ret` to the next instruction too. A
+/// path stops at bytes the decoder doesn't know, which are left as data, since
+/// guessing at their length would make whatever follows look like code.
+///
+/// Only offsets inside `code` are decoded, and an instruction never runs past
+/// the end of its range. Where two paths disagree about where an instruction
+/// starts, the first one decoded keeps the bytes.
+pub fn traverse(bytes: &[u8], code: &[Range<usize>], entries: &[usize]) -> Traversal {
+    let mut lines: BTreeMap<usize, Decoded> = BTreeMap::new();
+    let mut pending: Vec<usize> = entries.iter().rev().copied().collect();
+
+    while let Some(offset) = pending.pop() {
+        let Some(range) = code.iter().find(|range| range.contains(&offset)) else {
+            continue;
+        };
+
+        let inside_previous = lines
+            .range(..=offset)
+            .next_back()
+            .is_some_and(|(start, decoded)| start + decoded.size > offset);
+        if inside_previous {
+            continue;
+        }
+
+        let decoded = decode_at(&bytes[..range.end.min(bytes.len())], offset);
+        if matches!(decoded.instruction, Instruction::Noop) {
+            continue;
+        }
+        if lines
+            .range(offset + 1..offset + decoded.size)
+            .next()
+            .is_some()
+        {
+            continue;
+        }
+
+        pending.extend(decoded.jump_target());
+        pending.extend(decoded.fallthrough());
+        lines.insert(offset, decoded);
+    }
+
+    let mut data = vec![];
+    for range in code {
+        let mut start = range.start;
+        for (offset, decoded) in lines.range(range.clone()) {
+            if *offset > start {
+                data.push(start..*offset);
+            }
+            start = offset + decoded.size;
+        }
+        if range.end > start {
+            data.push(start..range.end);
+        }
+    }
+
+    Traversal {
+        lines: lines.into_values().collect(),
+        data,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Traverses from offset 0 with `code` the only code range.
+    fn traverse_range(bytes: &[u8], code: Range<usize>) -> Traversal {
+        traverse(bytes, std::slice::from_ref(&code), &[0])
+    }
+
+    fn offsets(traversal: &Traversal) -> Vec<usize> {
+        traversal
+            .lines
+            .iter()
+            .map(|decoded| decoded.offset)
+            .collect()
+    }
+
+    #[test]
+    fn bytes_jumped_over_are_data() {
+        // jmp short +1; db 0xFF; mov ax, 5; ret; db 0, 0
+        let bytes = [0xEB, 0x01, 0xFF, 0xB8, 0x05, 0x00, 0xC3, 0x00, 0x00];
+        let traversal = traverse_range(&bytes, 0..bytes.len());
+
+        assert_eq!(offsets(&traversal), vec![0, 3, 6]);
+        assert_eq!(traversal.data, vec![2..3, 7..9]);
+    }
+
+    #[test]
+    fn calls_and_branches_are_followed_both_ways() {
+        // call 5; ret; db 0; je 3; ret
+        let bytes = [0xE8, 0x02, 0x00, 0xC3, 0x00, 0x74, 0xFC, 0xC3];
+        let traversal = traverse_range(&bytes, 0..bytes.len());
+
+        assert_eq!(offsets(&traversal), vec![0, 3, 5, 7]);
+        assert_eq!(traversal.data, vec![4..5]);
+    }
+
+    #[test]
+    fn undecoded_bytes_end_the_path() {
+        // mov ax, 5; an unknown opcode; mov bx, 1
+        let bytes = [0xB8, 0x05, 0x00, 0x0F, 0xBB, 0x01, 0x00];
+        let traversal = traverse_range(&bytes, 0..bytes.len());
+
+        assert_eq!(offsets(&traversal), vec![0]);
+        assert_eq!(traversal.data, vec![3..7]);
+    }
+
+    #[test]
+    fn instructions_stay_inside_their_range() {
+        // mov ax, 5 split across the end of the code range
+        let bytes = [0xB8, 0x05, 0x00];
+        let traversal = traverse_range(&bytes, 0..2);
+
+        assert!(traversal.lines.is_empty());
+        assert_eq!(traversal.data, vec![0..2]);
+    }
+}